@@ -0,0 +1,189 @@
+//! TTL + LRU cache in front of the blob store.
+//!
+//! `handle_fetch` used to re-download a blob on every call. `BlobCache`
+//! keeps fetched bytes around for a fixed TTL (`DEFAULT_TTL`) and evicts
+//! least-recently-used entries once the total cached size crosses
+//! `max_size_bytes` (`DEFAULT_MAX_SIZE_BYTES`), never touching anything in
+//! the pinned set; neither limit can be changed at runtime. Eviction and
+//! invalidation drop only the cache's own copy: every fetched blob also
+//! stays in the underlying `MemStore`, which is never pruned.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use iroh_blobs::Hash;
+use tokio::sync::Mutex;
+
+use crate::AgentState;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(600);
+const DEFAULT_MAX_SIZE_BYTES: u64 = 256 * 1024 * 1024;
+
+struct CacheEntry {
+    bytes: Arc<Vec<u8>>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    size: u64,
+    last_access: chrono::DateTime<chrono::Utc>,
+}
+
+pub struct BlobCache {
+    entries: Mutex<HashMap<Hash, CacheEntry>>,
+    pinned: Mutex<HashSet<Hash>>,
+    max_size_bytes: u64,
+    default_ttl: Duration,
+}
+
+/// `*` matches everything; a trailing `*` is a prefix match; otherwise an
+/// exact match.
+fn matches_pattern(value: &str, pattern: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == pattern,
+    }
+}
+
+impl BlobCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            pinned: Mutex::new(HashSet::new()),
+            max_size_bytes: DEFAULT_MAX_SIZE_BYTES,
+            default_ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Return cached bytes for `hash` if present and not expired.
+    pub async fn get(&self, hash: &Hash) -> Option<Arc<Vec<u8>>> {
+        let mut entries = self.entries.lock().await;
+        let expired = matches!(entries.get(hash), Some(e) if e.expires_at.is_some_and(|t| t <= chrono::Utc::now()));
+        if expired {
+            entries.remove(hash);
+            return None;
+        }
+        let entry = entries.get_mut(hash)?;
+        entry.last_access = chrono::Utc::now();
+        Some(entry.bytes.clone())
+    }
+
+    /// Insert freshly downloaded bytes, then evict LRU entries until we're
+    /// back under budget.
+    pub async fn insert(&self, hash: Hash, bytes: Vec<u8>, state: &Arc<AgentState>) {
+        let size = bytes.len() as u64;
+        let expires_at =
+            Some(chrono::Utc::now() + chrono::Duration::from_std(self.default_ttl).unwrap_or_default());
+        self.entries.lock().await.insert(
+            hash,
+            CacheEntry {
+                bytes: Arc::new(bytes),
+                expires_at,
+                size,
+                last_access: chrono::Utc::now(),
+            },
+        );
+        self.evict_if_needed(state).await;
+    }
+
+    /// Pin `hash` so `evict_if_needed` never drops it, whether or not it's
+    /// currently cached.
+    pub async fn pin(&self, hash: Hash) {
+        self.pinned.lock().await.insert(hash);
+    }
+
+    /// Unpin `hash`, making it eligible for eviction again.
+    pub async fn unpin(&self, hash: &Hash) {
+        self.pinned.lock().await.remove(hash);
+    }
+
+    /// Remove an exact hash from the cache; emits `cache_evicted` if present.
+    pub async fn invalidate_hash(&self, hash: &Hash, state: &Arc<AgentState>) -> bool {
+        let removed = self.entries.lock().await.remove(hash).is_some();
+        if removed {
+            state
+                .emit_event("cache_evicted", serde_json::json!({ "hash": hash.to_string() }))
+                .await;
+        }
+        removed
+    }
+
+    /// Remove every cached hash whose string form matches a glob/prefix
+    /// `pattern` (`*` matches everything; a trailing `*` is a prefix match;
+    /// otherwise an exact match). Emits `cache_evicted` per dropped entry.
+    pub async fn invalidate_pattern(&self, pattern: &str, state: &Arc<AgentState>) -> Vec<Hash> {
+        let matched: Vec<Hash> = {
+            let mut entries = self.entries.lock().await;
+            let matched: Vec<Hash> = entries
+                .keys()
+                .filter(|hash| matches_pattern(&hash.to_string(), pattern))
+                .copied()
+                .collect();
+            for hash in &matched {
+                entries.remove(hash);
+            }
+            matched
+        };
+        for hash in &matched {
+            state
+                .emit_event("cache_evicted", serde_json::json!({ "hash": hash.to_string() }))
+                .await;
+        }
+        matched
+    }
+
+    async fn evict_if_needed(&self, state: &Arc<AgentState>) {
+        let evicted: Vec<Hash> = {
+            let mut entries = self.entries.lock().await;
+            let pinned = self.pinned.lock().await;
+            let mut total: u64 = entries.values().map(|e| e.size).sum();
+            let mut evicted = Vec::new();
+            while total > self.max_size_bytes {
+                let victim = entries
+                    .iter()
+                    .filter(|(hash, _)| !pinned.contains(*hash))
+                    .min_by_key(|(_, entry)| entry.last_access)
+                    .map(|(hash, _)| *hash);
+                match victim {
+                    Some(hash) => {
+                        if let Some(entry) = entries.remove(&hash) {
+                            total -= entry.size;
+                            evicted.push(hash);
+                        }
+                    }
+                    None => break, // everything left is pinned
+                }
+            }
+            evicted
+        };
+        for hash in evicted {
+            state
+                .emit_event("cache_evicted", serde_json::json!({ "hash": hash.to_string() }))
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_pattern_wildcard_matches_everything() {
+        assert!(matches_pattern("anything", "*"));
+        assert!(matches_pattern("", "*"));
+    }
+
+    #[test]
+    fn matches_pattern_prefix() {
+        assert!(matches_pattern("abc123", "abc*"));
+        assert!(!matches_pattern("xyz123", "abc*"));
+    }
+
+    #[test]
+    fn matches_pattern_exact() {
+        assert!(matches_pattern("abc123", "abc123"));
+        assert!(!matches_pattern("abc123", "abc12"));
+    }
+}
@@ -0,0 +1,522 @@
+//! Persistent job/run queue backed by SQLite.
+//!
+//! `jobs` is the durable unit of work (a repo/command spec); `runs` are
+//! individual attempts at executing a job, each progressing through
+//! `Pending -> Started -> Finished`. Splitting the two lets the same job be
+//! retried without losing history, and because everything lives in SQLite,
+//! in-flight work survives a crash and can be picked back up on the next
+//! startup.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use iroh::{Endpoint, EndpointAddr, EndpointId};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::notifier::{Notifier, RunEvent, RunTransition};
+use crate::{AgentApi, AgentState, RemoteArtifact};
+
+/// Lifecycle state of a single run attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunState {
+    Pending,
+    Started,
+    Finished,
+}
+
+impl RunState {
+    fn as_str(self) -> &'static str {
+        match self {
+            RunState::Pending => "pending",
+            RunState::Started => "started",
+            RunState::Finished => "finished",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "started" => RunState::Started,
+            "finished" => RunState::Finished,
+            _ => RunState::Pending,
+        }
+    }
+}
+
+/// Outcome of a finished run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunResult {
+    Success,
+    Failure,
+}
+
+impl RunResult {
+    fn as_str(self) -> &'static str {
+        match self {
+            RunResult::Success => "success",
+            RunResult::Failure => "failure",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "success" => RunResult::Success,
+            _ => RunResult::Failure,
+        }
+    }
+}
+
+/// A single run attempt of a job.
+#[derive(Debug, Clone, Serialize)]
+pub struct Run {
+    pub id: i64,
+    pub job_id: i64,
+    pub repo: String,
+    pub command: String,
+    pub state: RunState,
+    pub result: Option<RunResult>,
+    pub runner_id: Option<String>,
+    /// Arbitrary JSON attached at enqueue time, e.g. the webhook payload
+    /// summary that triggered this run.
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: String,  // RFC 3339
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+/// A build artifact produced by a run, backed by a blob in the blob store.
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub id: i64,
+    pub run_id: i64,
+    pub name: String,
+    pub hash: String,
+    pub size: u64,
+    pub created_at: String, // RFC 3339
+}
+
+/// Durable job/run store.
+pub struct DbCtx {
+    conn: Mutex<Connection>,
+}
+
+impl DbCtx {
+    /// Open (or create) the SQLite database at `path` and run migrations.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Arc<Self>> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                repo TEXT NOT NULL,
+                command TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                job_id INTEGER NOT NULL REFERENCES jobs(id),
+                state TEXT NOT NULL,
+                result TEXT,
+                runner_id TEXT,
+                metadata TEXT,
+                created_at TEXT NOT NULL,
+                started_at TEXT,
+                finished_at TEXT
+            );
+            CREATE TABLE IF NOT EXISTS artifacts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_id INTEGER NOT NULL REFERENCES runs(id),
+                name TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            ",
+        )?;
+        Ok(Arc::new(Self {
+            conn: Mutex::new(conn),
+        }))
+    }
+
+    /// Enqueue a new job with a single pending run, returning `(job_id, run_id)`.
+    /// `metadata`, if given, is attached to the run as-is (e.g. a summary of
+    /// the webhook payload that triggered it).
+    pub async fn enqueue(
+        &self,
+        repo: &str,
+        command: &str,
+        metadata: Option<serde_json::Value>,
+    ) -> rusqlite::Result<(i64, i64)> {
+        let conn = self.conn.lock().await;
+        let now = Utc::now().to_rfc3339();
+        let metadata = metadata.map(|m| m.to_string());
+        conn.execute(
+            "INSERT INTO jobs (repo, command, created_at) VALUES (?1, ?2, ?3)",
+            params![repo, command, now],
+        )?;
+        let job_id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO runs (job_id, state, metadata, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![job_id, RunState::Pending.as_str(), metadata, now],
+        )?;
+        Ok((job_id, conn.last_insert_rowid()))
+    }
+
+    /// Atomically claim up to `limit` pending runs for `runner_id`: select
+    /// the oldest candidates, then `UPDATE ... WHERE state = 'pending'` each
+    /// one individually and keep only the ones where that update actually
+    /// changed a row. This is what stops the same run being picked up twice
+    /// by adjacent poll ticks (or by another agent sharing this database).
+    async fn claim_pending_runs(&self, limit: usize, runner_id: &str) -> rusqlite::Result<Vec<Run>> {
+        let conn = self.conn.lock().await;
+        let candidates: Vec<Run> = {
+            let mut stmt = conn.prepare(
+                "SELECT r.id, r.job_id, j.repo, j.command, r.state, r.result, r.runner_id,
+                        r.metadata, r.created_at, r.started_at, r.finished_at
+                 FROM runs r JOIN jobs j ON j.id = r.job_id
+                 WHERE r.state = ?1 ORDER BY r.created_at ASC LIMIT ?2",
+            )?;
+            stmt.query_map(params![RunState::Pending.as_str(), limit as i64], row_to_run)?
+                .collect::<rusqlite::Result<_>>()?
+        };
+
+        let now = Utc::now().to_rfc3339();
+        let mut claimed = Vec::with_capacity(candidates.len());
+        for mut run in candidates {
+            let changed = conn.execute(
+                "UPDATE runs SET state = ?1, runner_id = ?2, started_at = ?3
+                 WHERE id = ?4 AND state = ?5",
+                params![
+                    RunState::Started.as_str(),
+                    runner_id,
+                    now,
+                    run.id,
+                    RunState::Pending.as_str(),
+                ],
+            )?;
+            if changed == 1 {
+                run.state = RunState::Started;
+                run.runner_id = Some(runner_id.to_string());
+                run.started_at = Some(now.clone());
+                claimed.push(run);
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Mark a run `Finished` with its result.
+    async fn mark_finished(&self, run_id: i64, result: RunResult) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE runs SET state = ?1, result = ?2, finished_at = ?3 WHERE id = ?4",
+            params![
+                RunState::Finished.as_str(),
+                result.as_str(),
+                Utc::now().to_rfc3339(),
+                run_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// List every run for a job, most recent first.
+    pub async fn list_runs(&self, job_id: i64) -> rusqlite::Result<Vec<Run>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT r.id, r.job_id, j.repo, j.command, r.state, r.result, r.runner_id,
+                    r.metadata, r.created_at, r.started_at, r.finished_at
+             FROM runs r JOIN jobs j ON j.id = r.job_id
+             WHERE r.job_id = ?1 ORDER BY r.created_at DESC",
+        )?;
+        let rows = stmt.query_map(params![job_id], row_to_run)?;
+        rows.collect()
+    }
+
+    /// Record a newly uploaded artifact against its owning run.
+    pub async fn record_artifact(
+        &self,
+        run_id: i64,
+        name: &str,
+        hash: &str,
+        size: u64,
+    ) -> rusqlite::Result<i64> {
+        let conn = self.conn.lock().await;
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO artifacts (run_id, name, hash, size, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![run_id, name, hash, size as i64, now],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// List every artifact uploaded for a run, most recent first.
+    pub async fn list_artifacts(&self, run_id: i64) -> rusqlite::Result<Vec<Artifact>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, run_id, name, hash, size, created_at FROM artifacts
+             WHERE run_id = ?1 ORDER BY created_at DESC",
+        )?;
+        let rows = stmt.query_map(params![run_id], |row| {
+            let size: i64 = row.get(4)?;
+            Ok(Artifact {
+                id: row.get(0)?,
+                run_id: row.get(1)?,
+                name: row.get(2)?,
+                hash: row.get(3)?,
+                size: size as u64,
+                created_at: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Fetch a single run by id.
+    pub async fn run_status(&self, run_id: i64) -> rusqlite::Result<Option<Run>> {
+        let conn = self.conn.lock().await;
+        conn.query_row(
+            "SELECT r.id, r.job_id, j.repo, j.command, r.state, r.result, r.runner_id,
+                    r.metadata, r.created_at, r.started_at, r.finished_at
+             FROM runs r JOIN jobs j ON j.id = r.job_id
+             WHERE r.id = ?1",
+            params![run_id],
+            row_to_run,
+        )
+        .optional()
+    }
+}
+
+fn row_to_run(row: &rusqlite::Row) -> rusqlite::Result<Run> {
+    let state: String = row.get(4)?;
+    let result: Option<String> = row.get(5)?;
+    let metadata: Option<String> = row.get(7)?;
+    Ok(Run {
+        id: row.get(0)?,
+        job_id: row.get(1)?,
+        repo: row.get(2)?,
+        command: row.get(3)?,
+        state: RunState::parse(&state),
+        result: result.map(|r| RunResult::parse(&r)),
+        runner_id: row.get(6)?,
+        metadata: metadata.and_then(|m| serde_json::from_str(&m).ok()),
+        created_at: row.get(8)?,
+        started_at: row.get(9)?,
+        finished_at: row.get(10)?,
+    })
+}
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Spawn the background task that picks up `Pending` runs and executes
+/// them. Claimed runs are fanned out round-robin across the currently
+/// connected `remote_clients` via the `ExecuteRun` RPC; with no remote peers
+/// connected, a lone agent falls back to running everything locally. On
+/// start, runs this host had claimed but never finished are put back to
+/// `Pending` first, so work interrupted by a crash is dispatched again.
+pub fn spawn_dispatcher(
+    db: Arc<DbCtx>,
+    host_id: String,
+    remote_clients: Arc<Mutex<HashMap<String, AgentApi>>>,
+    notifier: Arc<Notifier>,
+    state: Arc<AgentState>,
+    endpoint: Endpoint,
+    blobs: iroh_blobs::BlobsProtocol,
+) {
+    tokio::spawn(async move {
+        {
+            let conn = db.conn.lock().await;
+            if let Err(e) = conn.execute(
+                "UPDATE runs SET state = ?1, runner_id = NULL, started_at = NULL
+                 WHERE state = ?2 AND runner_id = ?3",
+                params![RunState::Pending.as_str(), RunState::Started.as_str(), host_id],
+            ) {
+                warn!("job queue: failed to requeue interrupted runs: {}", e);
+            }
+        }
+
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let runners: Vec<(String, AgentApi)> = remote_clients
+                .lock()
+                .await
+                .iter()
+                .map(|(endpoint_id, api)| (endpoint_id.clone(), api.clone_handle()))
+                .collect();
+            let capacity = runners.len().max(1);
+
+            let runs = match db.claim_pending_runs(capacity, &host_id).await {
+                Ok(runs) => runs,
+                Err(e) => {
+                    warn!("job queue: failed to claim pending runs: {}", e);
+                    continue;
+                }
+            };
+
+            for (i, run) in runs.into_iter().enumerate() {
+                let db = db.clone();
+                let notifier = notifier.clone();
+                let state = state.clone();
+                let endpoint = endpoint.clone();
+                let blobs = blobs.clone();
+                // `runners.get` on an empty Vec with capacity == 1 always
+                // misses, naturally falling back to local execution below.
+                let runner = runners
+                    .get(i % capacity)
+                    .map(|(endpoint_id, api)| (endpoint_id.clone(), api.clone_handle()));
+                tokio::spawn(async move {
+                    debug!("job queue: running run {} ({})", run.id, run.command);
+                    notifier
+                        .fire(RunEvent {
+                            run_id: run.id,
+                            repo: run.repo.clone(),
+                            command: run.command.clone(),
+                            transition: RunTransition::Started,
+                            exit_status: None,
+                        })
+                        .await;
+
+                    let (success, exit_status) = match runner {
+                        Some((runner_id, api)) => {
+                            let token = state.current_secret().await.unwrap_or_default();
+                            match api
+                                .execute_run(run.repo.clone(), run.command.clone(), run.id, token)
+                                .await
+                            {
+                                Ok(resp) => {
+                                    fetch_remote_artifacts(
+                                        &endpoint,
+                                        &blobs,
+                                        &runner_id,
+                                        &db,
+                                        run.id,
+                                        resp.artifacts,
+                                    )
+                                    .await;
+                                    (resp.success, None)
+                                }
+                                Err(e) => {
+                                    warn!(
+                                        "job queue: dispatching run {} to {} failed: {}",
+                                        run.id, runner_id, e
+                                    );
+                                    (false, None)
+                                }
+                            }
+                        }
+                        None => {
+                            let status = tokio::process::Command::new("sh")
+                                .arg("-c")
+                                .arg(&run.command)
+                                .stdin(Stdio::null())
+                                .stdout(Stdio::null())
+                                .stderr(Stdio::null())
+                                .status()
+                                .await;
+                            (
+                                matches!(&status, Ok(status) if status.success()),
+                                status.ok().map(|s| s.to_string()),
+                            )
+                        }
+                    };
+
+                    let (result, transition) = if success {
+                        (RunResult::Success, RunTransition::Succeeded)
+                    } else {
+                        (RunResult::Failure, RunTransition::Failed)
+                    };
+                    if let Err(e) = db.mark_finished(run.id, result).await {
+                        warn!("job queue: failed to mark run {} finished: {}", run.id, e);
+                    }
+                    notifier
+                        .fire(RunEvent {
+                            run_id: run.id,
+                            repo: run.repo,
+                            command: run.command,
+                            transition,
+                            exit_status,
+                        })
+                        .await;
+                });
+            }
+        }
+    });
+}
+
+/// Fetch every artifact a remotely executed run reported back and record it
+/// against `run_id` in our own database. The remote added these blobs to its
+/// own store, not ours, so each one has to be pulled over by hash before
+/// `record_artifact` can point at it the same way a local upload would.
+async fn fetch_remote_artifacts(
+    endpoint: &Endpoint,
+    blobs: &iroh_blobs::BlobsProtocol,
+    runner_id: &str,
+    db: &DbCtx,
+    run_id: i64,
+    artifacts: Vec<RemoteArtifact>,
+) {
+    if artifacts.is_empty() {
+        return;
+    }
+    let peer_id: EndpointId = match runner_id.parse() {
+        Ok(id) => id,
+        Err(e) => {
+            warn!("job queue: invalid runner id {}: {}", runner_id, e);
+            return;
+        }
+    };
+
+    for artifact in artifacts {
+        let hash: iroh_blobs::Hash = match artifact.hash.parse() {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!(
+                    "job queue: invalid artifact hash {:?} from {}: {}",
+                    artifact.hash, runner_id, e
+                );
+                continue;
+            }
+        };
+        let conn = match endpoint
+            .connect(EndpointAddr::new(peer_id), iroh_blobs::ALPN)
+            .await
+        {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!(
+                    "job queue: failed to connect to {} to fetch artifact {}: {}",
+                    runner_id, artifact.name, e
+                );
+                continue;
+            }
+        };
+        if let Err(e) = blobs
+            .store()
+            .remote()
+            .fetch(conn, iroh_blobs::HashAndFormat::raw(hash))
+            .await
+        {
+            warn!(
+                "job queue: failed to fetch artifact {} from {}: {}",
+                artifact.name, runner_id, e
+            );
+            continue;
+        }
+        if let Err(e) = db
+            .record_artifact(run_id, &artifact.name, &artifact.hash, artifact.size)
+            .await
+        {
+            warn!(
+                "job queue: failed to record artifact {} for run {}: {}",
+                artifact.name, run_id, e
+            );
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Streaming task execution.
+//!
+//! `Command::Exec` spawns a child process via `sh -c`, merges its stdout and
+//! stderr into framed output lines emitted as they arrive instead of
+//! buffering the whole run, and finishes with a terminal exit frame. Tasks
+//! are tracked in a registry keyed by the command `id` so `Command::Cancel`
+//! can kill one mid-flight, and because each task streams from its own
+//! tokio task, several concurrent execs can interleave their output on
+//! stdout without blocking the main command loop.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command as ProcessCommand};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+#[derive(Debug, Serialize)]
+struct ExecOutput<'a> {
+    id: &'a str,
+    kind: &'static str,
+    line: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct ExecExit<'a> {
+    id: &'a str,
+    kind: &'static str,
+    code: Option<i32>,
+}
+
+/// A running task, identified by the `id` of the `Exec` command that
+/// started it.
+pub struct TaskHandle {
+    child: Arc<Mutex<Child>>,
+}
+
+impl TaskHandle {
+    /// Kill the underlying child process.
+    pub async fn cancel(&self) -> std::io::Result<()> {
+        self.child.lock().await.start_kill()
+    }
+}
+
+/// Spawn `script` under `sh -c`, streaming merged stdout/stderr lines to
+/// stdout as framed JSON, and register a handle that can cancel it under
+/// `id` in `tasks` before the reader task that removes it on exit is even
+/// started, so a script that finishes instantly can never race its own
+/// registration.
+pub async fn spawn(
+    id: String,
+    script: String,
+    tasks: Arc<Mutex<HashMap<String, TaskHandle>>>,
+) -> anyhow::Result<()> {
+    let mut cmd = ProcessCommand::new("sh");
+    cmd.arg("-c")
+        .arg(&script)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn()?;
+    let stdout = child.stdout.take().expect("stdout was piped");
+    let stderr = child.stderr.take().expect("stderr was piped");
+
+    let child = Arc::new(Mutex::new(child));
+    let wait_child = child.clone();
+    let task_id = id.clone();
+
+    tasks.lock().await.insert(id.clone(), TaskHandle { child });
+
+    tokio::spawn(async move {
+        let mut stdout_lines = BufReader::new(stdout).lines();
+        let mut stderr_lines = BufReader::new(stderr).lines();
+        let mut stdout_done = false;
+        let mut stderr_done = false;
+
+        while !(stdout_done && stderr_done) {
+            tokio::select! {
+                line = stdout_lines.next_line(), if !stdout_done => match line {
+                    Ok(Some(line)) => emit_output(&task_id, &line),
+                    Ok(None) => stdout_done = true,
+                    Err(e) => {
+                        warn!("exec {}: stdout read error: {}", task_id, e);
+                        stdout_done = true;
+                    }
+                },
+                line = stderr_lines.next_line(), if !stderr_done => match line {
+                    Ok(Some(line)) => emit_output(&task_id, &line),
+                    Ok(None) => stderr_done = true,
+                    Err(e) => {
+                        warn!("exec {}: stderr read error: {}", task_id, e);
+                        stderr_done = true;
+                    }
+                },
+            }
+        }
+
+        let status = wait_child.lock().await.wait().await;
+        let code = status.ok().and_then(|s| s.code());
+        emit_exit(&task_id, code);
+        tasks.lock().await.remove(&id);
+    });
+
+    Ok(())
+}
+
+fn emit_output(id: &str, line: &str) {
+    crate::emit_json(&ExecOutput {
+        id,
+        kind: "output",
+        line,
+    });
+}
+
+fn emit_exit(id: &str, code: Option<i32>) {
+    crate::emit_json(&ExecExit {
+        id,
+        kind: "exit",
+        code,
+    });
+}
